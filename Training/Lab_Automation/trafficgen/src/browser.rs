@@ -1,51 +1,212 @@
-use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::Rng;
 use reqwest::Client;
 
-const USER_AGENTS: &[&str] = &[
-    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
-    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
-    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0",
-    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.2 Safari/605.1.15",
-    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
-    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36 Edg/131.0.0.0",
-    "Mozilla/5.0 (X11; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0",
-    "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0",
-    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/130.0.0.0 Safari/537.36",
-    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/130.0.0.0 Safari/537.36",
-];
+use crate::network::resolver::{DnsMode, DohResolver};
 
 const ACCEPT_HEADER: &str =
     "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,image/webp,image/apng,*/*;q=0.8";
-const ACCEPT_LANGUAGE: &str = "en-US,en;q=0.9";
 
-pub fn random_user_agent() -> &'static str {
-    let mut rng = rand::thread_rng();
-    USER_AGENTS.choose(&mut rng).expect("USER_AGENTS is non-empty")
+/// A coherent OS/browser identity: a `User-Agent` plus the client-hint and
+/// `Accept-Language` headers a real client with that UA would actually send.
+/// Kept as one unit so a Chrome UA never ships alongside Safari's (absent)
+/// `Sec-CH-UA` headers or the wrong `Accept-Language`. `id` is the stable
+/// key operators use to bias selection via `Config::browser_profile_weights`;
+/// `weight` is the default market-share-ish weight within its vendor pool.
+pub struct DeviceProfile {
+    pub id: &'static str,
+    pub user_agent: &'static str,
+    pub sec_ch_ua: Option<&'static str>,
+    pub sec_ch_ua_platform: Option<&'static str>,
+    pub accept_language: &'static str,
+    pub weight: u32,
 }
 
-pub fn build_client() -> Result<Client, reqwest::Error> {
-    let ua = random_user_agent();
-    Client::builder()
-        .user_agent(ua)
+const MACOS_SAFARI_PROFILES: &[DeviceProfile] = &[
+    DeviceProfile {
+        id: "macos_safari",
+        user_agent: "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/18.2 Safari/605.1.15",
+        sec_ch_ua: None,
+        sec_ch_ua_platform: None,
+        accept_language: "en-US,en;q=0.9",
+        weight: 3,
+    },
+    DeviceProfile {
+        id: "macos_chrome",
+        user_agent: "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
+        sec_ch_ua: Some("\"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\", \"Google Chrome\";v=\"131\""),
+        sec_ch_ua_platform: Some("\"macOS\""),
+        accept_language: "en-US,en;q=0.9",
+        weight: 2,
+    },
+];
+
+const WINDOWS_CHROME_PROFILES: &[DeviceProfile] = &[
+    DeviceProfile {
+        id: "windows_chrome",
+        user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
+        sec_ch_ua: Some("\"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\", \"Google Chrome\";v=\"131\""),
+        sec_ch_ua_platform: Some("\"Windows\""),
+        accept_language: "en-US,en;q=0.9",
+        weight: 5,
+    },
+    DeviceProfile {
+        id: "windows_edge",
+        user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36 Edg/131.0.0.0",
+        sec_ch_ua: Some("\"Microsoft Edge\";v=\"131\", \"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\""),
+        sec_ch_ua_platform: Some("\"Windows\""),
+        accept_language: "en-US,en;q=0.9",
+        weight: 2,
+    },
+    DeviceProfile {
+        id: "windows_firefox",
+        user_agent: "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0",
+        sec_ch_ua: None,
+        sec_ch_ua_platform: None,
+        accept_language: "en-US,en;q=0.9",
+        weight: 1,
+    },
+];
+
+const ANDROID_CHROME_PROFILES: &[DeviceProfile] = &[DeviceProfile {
+    id: "android_chrome",
+    user_agent: "Mozilla/5.0 (Linux; Android 14; SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Mobile Safari/537.36",
+    sec_ch_ua: Some("\"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\", \"Google Chrome\";v=\"131\""),
+    sec_ch_ua_platform: Some("\"Android\""),
+    accept_language: "en-US,en;q=0.9",
+    weight: 1,
+}];
+
+const LINUX_DESKTOP_PROFILES: &[DeviceProfile] = &[
+    DeviceProfile {
+        id: "linux_chrome",
+        user_agent: "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36",
+        sec_ch_ua: Some("\"Chromium\";v=\"131\", \"Not_A Brand\";v=\"24\", \"Google Chrome\";v=\"131\""),
+        sec_ch_ua_platform: Some("\"Linux\""),
+        accept_language: "en-US,en;q=0.9",
+        weight: 2,
+    },
+    DeviceProfile {
+        id: "linux_firefox",
+        user_agent: "Mozilla/5.0 (X11; Ubuntu; Linux x86_64; rv:133.0) Gecko/20100101 Firefox/133.0",
+        sec_ch_ua: None,
+        sec_ch_ua_platform: None,
+        accept_language: "en-US,en;q=0.9",
+        weight: 1,
+    },
+];
+
+/// Picks the device profile pool that plausibly carries a given OUI vendor
+/// name: laptop/phone vendors map to the OS their hardware actually ships
+/// with; network-gear vendors (Cisco, Ubiquiti, ...) and pinned/unknown
+/// vendors fall back to a generic Linux desktop rather than asserting a
+/// consumer OS they're unlikely to run.
+fn profiles_for_vendor(vendor: &str) -> &'static [DeviceProfile] {
+    match vendor {
+        "Apple" => MACOS_SAFARI_PROFILES,
+        "Dell" | "HP" | "HPE" | "Lenovo" | "Intel" | "Microsoft" | "VMware" | "Broadcom"
+        | "Qualcomm" | "Supermicro" => WINDOWS_CHROME_PROFILES,
+        "Samsung" | "Huawei" => ANDROID_CHROME_PROFILES,
+        _ => LINUX_DESKTOP_PROFILES,
+    }
+}
+
+/// Picks a `DeviceProfile` for the given OUI vendor name (as returned by
+/// `mac::generate_mac`/`mac::fixed_mac`), weighted so the client fingerprint
+/// matches the device class its MAC claims to be while still reflecting a
+/// realistic browser mix within that class. `weight_overrides`, keyed by
+/// `DeviceProfile::id`, lets an operator bias that mix via
+/// `Config::browser_profile_weights` toward their target population;
+/// profiles without an override keep their built-in weight.
+pub fn profile_for_vendor(
+    vendor: &str,
+    weight_overrides: Option<&HashMap<String, u32>>,
+) -> &'static DeviceProfile {
+    let profiles = profiles_for_vendor(vendor);
+    let weights: Vec<u32> = profiles
+        .iter()
+        .map(|p| {
+            weight_overrides
+                .and_then(|overrides| overrides.get(p.id))
+                .copied()
+                .unwrap_or(p.weight)
+        })
+        .collect();
+    let total: u32 = weights.iter().sum();
+    if total == 0 {
+        return &profiles[0];
+    }
+    let mut pick = rand::thread_rng().gen_range(0..total);
+    for (profile, weight) in profiles.iter().zip(weights.iter()) {
+        if pick < *weight {
+            return profile;
+        }
+        pick -= *weight;
+    }
+    &profiles[profiles.len() - 1]
+}
+
+/// Builds an HTTP client for a virtual user, carrying `profile`'s UA and
+/// client-hint headers so the client fingerprint matches the device
+/// identity its MAC vendor implies. When `bind_addr` is set, the client's
+/// outbound connections are pinned to that local source address (see
+/// `reqwest::ClientBuilder::local_address`) instead of whatever the
+/// adapter's current address happens to be. `dns_mode` selects between the
+/// system resolver and a `DohResolver` override, per `Config::dns_mode`.
+pub fn build_client(
+    profile: &DeviceProfile,
+    bind_addr: Option<IpAddr>,
+    dns_mode: &DnsMode,
+) -> Result<Client, reqwest::Error> {
+    let mut builder = Client::builder()
+        .user_agent(profile.user_agent)
         .danger_accept_invalid_certs(true)
         .redirect(reqwest::redirect::Policy::limited(10))
-        .connect_timeout(std::time::Duration::from_secs(30))
-        .timeout(std::time::Duration::from_secs(60))
+        .connect_timeout(Duration::from_secs(30))
+        .timeout(Duration::from_secs(60))
         .cookie_store(true)
+        .local_address(bind_addr)
         .default_headers({
             let mut headers = reqwest::header::HeaderMap::new();
             headers.insert(reqwest::header::ACCEPT, ACCEPT_HEADER.parse().unwrap());
             headers.insert(
                 reqwest::header::ACCEPT_LANGUAGE,
-                ACCEPT_LANGUAGE.parse().unwrap(),
+                profile.accept_language.parse().unwrap(),
             );
             headers.insert(
                 reqwest::header::ACCEPT_ENCODING,
                 "gzip, deflate, br".parse().unwrap(),
             );
+            if let Some(sec_ch_ua) = profile.sec_ch_ua {
+                headers.insert("Sec-CH-UA", sec_ch_ua.parse().unwrap());
+            }
+            if let Some(sec_ch_ua_platform) = profile.sec_ch_ua_platform {
+                headers.insert("Sec-CH-UA-Platform", sec_ch_ua_platform.parse().unwrap());
+            }
             headers
-        })
-        .build()
+        });
+
+    if let DnsMode::DoH {
+        upstreams,
+        strategy,
+        ttl_floor_secs,
+        ttl_ceiling_secs,
+    } = dns_mode
+    {
+        let resolver = DohResolver::new(
+            upstreams.clone(),
+            *strategy,
+            Duration::from_secs(*ttl_floor_secs),
+            Duration::from_secs(*ttl_ceiling_secs),
+        );
+        builder = builder.dns_resolver(Arc::new(resolver));
+    }
+
+    builder.build()
 }
 
 #[cfg(test)]
@@ -53,22 +214,73 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_random_user_agent_returns_valid() {
-        let ua = random_user_agent();
-        assert!(USER_AGENTS.contains(&ua), "Unexpected user agent: {}", ua);
+    fn test_profile_for_vendor_apple_is_macos() {
+        for _ in 0..20 {
+            let profile = profile_for_vendor("Apple", None);
+            assert!(profile.user_agent.contains("Macintosh"));
+        }
+    }
+
+    #[test]
+    fn test_profile_for_vendor_dell_is_windows() {
+        for _ in 0..20 {
+            let profile = profile_for_vendor("Dell", None);
+            assert!(profile.user_agent.contains("Windows"));
+        }
     }
 
     #[test]
-    fn test_random_user_agent_varies() {
-        let agents: Vec<&str> = (0..20).map(|_| random_user_agent()).collect();
-        let first = agents[0];
-        let all_same = agents.iter().all(|a| *a == first);
-        assert!(!all_same, "All 20 user agents were identical");
+    fn test_profile_for_vendor_samsung_is_android() {
+        let profile = profile_for_vendor("Samsung", None);
+        assert!(profile.user_agent.contains("Android"));
+        assert_eq!(profile.sec_ch_ua_platform, Some("\"Android\""));
+    }
+
+    #[test]
+    fn test_profile_for_vendor_unknown_falls_back_to_linux() {
+        let profile = profile_for_vendor("pinned", None);
+        assert!(LINUX_DESKTOP_PROFILES
+            .iter()
+            .any(|p| p.user_agent == profile.user_agent));
+    }
+
+    #[test]
+    fn test_profile_for_vendor_weight_override_pins_selection() {
+        let mut overrides = HashMap::new();
+        overrides.insert("windows_firefox".to_string(), 1);
+        overrides.insert("windows_chrome".to_string(), 0);
+        overrides.insert("windows_edge".to_string(), 0);
+        for _ in 0..20 {
+            let profile = profile_for_vendor("Dell", Some(&overrides));
+            assert_eq!(profile.id, "windows_firefox");
+        }
     }
 
     #[test]
     fn test_build_client_succeeds() {
-        let client = build_client();
+        let profile = profile_for_vendor("Dell", None);
+        let client = build_client(profile, None, &DnsMode::SystemResolvConf);
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn test_build_client_with_bind_addr_succeeds() {
+        let profile = profile_for_vendor("Apple", None);
+        let bind_addr: IpAddr = "10.0.0.50".parse().unwrap();
+        let client = build_client(profile, Some(bind_addr), &DnsMode::SystemResolvConf);
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn test_build_client_with_doh_resolver_succeeds() {
+        let profile = profile_for_vendor("Dell", None);
+        let dns_mode = DnsMode::DoH {
+            upstreams: vec!["https://dns.quad9.net/dns-query".to_string()],
+            strategy: crate::network::resolver::LookupIpStrategy::Ipv4thenIpv6,
+            ttl_floor_secs: 30,
+            ttl_ceiling_secs: 3600,
+        };
+        let client = build_client(profile, None, &dns_mode);
         assert!(client.is_ok());
     }
 }
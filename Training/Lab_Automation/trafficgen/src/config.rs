@@ -1,9 +1,33 @@
 // Runtime configuration
 
+use std::collections::HashMap;
 use std::net::IpAddr;
 
 use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
 
+use crate::network::resolver::DnsMode;
+use crate::network::IpAssignMode;
+use crate::scope::Scope;
+
+pub fn default_dns_mode() -> DnsMode {
+    DnsMode::SystemResolvConf
+}
+
+/// A reproducible MAC/IP pair for one virtual user, so a cyber-range
+/// scenario can be replayed identically across restarts instead of
+/// drawing fresh random identities every run.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PinnedIdentity {
+    pub mac: String,
+    pub ip: IpAddr,
+}
+
+pub fn default_ledger_path() -> String {
+    "rotation_ledger.jsonl".to_string()
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Config {
     pub sites: Vec<url::Url>,
     pub adapter: String,
@@ -15,6 +39,76 @@ pub struct Config {
     pub site_switch_mins: u64,
     pub num_users: usize,
     pub max_depth: usize,
+    pub ip_mode: IpAssignMode,
+    /// Pin the adapter's rotated MAC to this value instead of generating a
+    /// fresh one on every rotation (`mac::generate_mac` for the initial
+    /// rotation, `mac::generate_locally_administered_mac` for scheduled ones).
+    #[serde(default)]
+    pub host_mac: Option<String>,
+    /// When set, the rotation scheduler fetches this echo endpoint (expected
+    /// to respond with the caller's public IP as its plain-text body) after
+    /// each rotation, to confirm the rotation actually changed the egress
+    /// identity rather than e.g. landing behind the same NAT mapping.
+    #[serde(default)]
+    pub egress_check_url: Option<String>,
+    /// Installs an nftables egress firewall restricting the rotated
+    /// adapter to `scope`'s CIDR ranges for the lifetime of the run, as a
+    /// hard backstop beneath the crawler-level `scope::Scope` filtering.
+    /// Requires `scope` to have at least one CIDR rule; a domain-only
+    /// scope can't be enforced at the packet level.
+    #[serde(default)]
+    pub enforce_scope_firewall: bool,
+    /// Sidecar file that rotation events are appended to as an auditable
+    /// ledger (see `ledger::append_rotation`).
+    #[serde(default = "default_ledger_path")]
+    pub ledger_path: String,
+    /// Overrides the built-in weight of individual `browser::DeviceProfile`s
+    /// (keyed by `DeviceProfile::id`, e.g. `"windows_chrome"`) so operators
+    /// can bias the emulated browser mix toward their target population.
+    /// Profiles without an entry here keep their default weight.
+    #[serde(default)]
+    pub browser_profile_weights: Option<HashMap<String, u32>>,
+    /// How `browser::build_client` resolves hostnames. Defaults to the
+    /// system resolver so existing configs without this key keep working.
+    #[serde(default = "default_dns_mode")]
+    pub dns_mode: DnsMode,
+    /// Domain-suffix and CIDR allow/deny lists bounding what the crawler
+    /// will request (see `crawler::filter_in_scope`). Defaults to an empty
+    /// scope, i.e. no restriction beyond `filter_same_domain`.
+    #[serde(default)]
+    pub scope: Scope,
+    /// Fixed per-user MAC/IP identities, indexed by user number. When
+    /// shorter than `num_users`, the remaining users still get randomized
+    /// identities.
+    #[serde(default)]
+    pub pinned_identities: Option<Vec<PinnedIdentity>>,
+}
+
+/// Loads a `Config` from a TOML or JSON file (selected by extension), for
+/// unattended runs via `trafficgen --config run.toml`.
+pub fn load_config_file(path: &str) -> Result<Config, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+    if path.ends_with(".json") {
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse JSON config {}: {}", path, e))
+    } else {
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse TOML config {}: {}", path, e))
+    }
+}
+
+/// Saves a `Config` to a TOML or JSON file (selected by extension), so an
+/// interactively-built configuration can be replayed with `--config`.
+pub fn save_config_file(path: &str, config: &Config) -> Result<(), String> {
+    let content = if path.ends_with(".json") {
+        serde_json::to_string_pretty(config)
+            .map_err(|e| format!("Failed to serialize config as JSON: {}", e))?
+    } else {
+        toml::to_string_pretty(config)
+            .map_err(|e| format!("Failed to serialize config as TOML: {}", e))?
+    };
+    std::fs::write(path, content).map_err(|e| format!("Failed to write config file {}: {}", path, e))
 }
 
 pub fn parse_sites(input: &str) -> Vec<url::Url> {
@@ -52,6 +146,15 @@ mod tests {
             site_switch_mins: 30,
             num_users: 3,
             max_depth: 5,
+            ip_mode: IpAssignMode::AdapterRotation,
+            host_mac: None,
+            egress_check_url: None,
+            enforce_scope_firewall: false,
+            pinned_identities: None,
+            ledger_path: default_ledger_path(),
+            browser_profile_weights: None,
+            dns_mode: default_dns_mode(),
+            scope: Scope::default(),
         };
         assert_eq!(config.sites.len(), 1);
         assert_eq!(config.adapter, "eth0");
@@ -59,6 +162,80 @@ mod tests {
         assert_eq!(config.max_depth, 5);
     }
 
+    fn sample_config() -> Config {
+        Config {
+            sites: vec![url::Url::parse("https://example.com").unwrap()],
+            adapter: "eth0".to_string(),
+            cidr: IpNetwork::from_str("10.0.0.0/24").unwrap(),
+            dns: IpAddr::from_str("8.8.8.8").unwrap(),
+            gateway: IpAddr::from_str("10.0.0.1").unwrap(),
+            rotation_interval_mins: 15,
+            request_delay_mins: 2.0,
+            site_switch_mins: 30,
+            num_users: 3,
+            max_depth: 5,
+            ip_mode: IpAssignMode::AdapterRotation,
+            host_mac: Some("AA:BB:CC:DD:EE:FF".to_string()),
+            egress_check_url: Some("https://api.ipify.org".to_string()),
+            enforce_scope_firewall: true,
+            pinned_identities: Some(vec![PinnedIdentity {
+                mac: "AA:BB:CC:00:00:01".to_string(),
+                ip: IpAddr::from_str("10.0.0.50").unwrap(),
+            }]),
+            ledger_path: default_ledger_path(),
+            browser_profile_weights: Some(HashMap::from([("windows_chrome".to_string(), 10)])),
+            dns_mode: DnsMode::DoH {
+                upstreams: vec!["https://dns.quad9.net/dns-query".to_string()],
+                strategy: crate::network::resolver::LookupIpStrategy::Ipv4thenIpv6,
+                ttl_floor_secs: 30,
+                ttl_ceiling_secs: 3600,
+            },
+            scope: Scope {
+                allow_domains: vec!["example.com".to_string()],
+                deny_domains: vec!["blocked.example.com".to_string()],
+                allow_cidrs: vec![],
+                deny_cidrs: vec![IpNetwork::from_str("10.0.1.0/24").unwrap()],
+            },
+        }
+    }
+
+    #[test]
+    fn test_config_toml_round_trip() {
+        let dir = std::env::temp_dir().join("trafficgen_test_config_round_trip.toml");
+        let path = dir.to_str().unwrap();
+        save_config_file(path, &sample_config()).expect("save should succeed");
+        let loaded = load_config_file(path).expect("load should succeed");
+        assert_eq!(loaded.adapter, "eth0");
+        assert_eq!(loaded.host_mac, Some("AA:BB:CC:DD:EE:FF".to_string()));
+        assert_eq!(loaded.egress_check_url, Some("https://api.ipify.org".to_string()));
+        assert!(loaded.enforce_scope_firewall);
+        assert_eq!(loaded.scope.allow_domains, vec!["example.com".to_string()]);
+        assert_eq!(loaded.scope.deny_cidrs.len(), 1);
+        assert_eq!(loaded.pinned_identities.unwrap().len(), 1);
+        assert_eq!(
+            loaded.browser_profile_weights.unwrap().get("windows_chrome"),
+            Some(&10)
+        );
+        match loaded.dns_mode {
+            DnsMode::DoH { upstreams, .. } => {
+                assert_eq!(upstreams, vec!["https://dns.quad9.net/dns-query".to_string()])
+            }
+            DnsMode::SystemResolvConf => panic!("expected DoH mode to round-trip"),
+        }
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn test_config_json_round_trip() {
+        let dir = std::env::temp_dir().join("trafficgen_test_config_round_trip.json");
+        let path = dir.to_str().unwrap();
+        save_config_file(path, &sample_config()).expect("save should succeed");
+        let loaded = load_config_file(path).expect("load should succeed");
+        assert_eq!(loaded.num_users, 3);
+        assert_eq!(loaded.ledger_path, default_ledger_path());
+        std::fs::remove_file(path).ok();
+    }
+
     #[test]
     fn test_parse_sites_valid() {
         let input = "https://10.0.0.1/login\nhttp://10.0.0.2:8080/index\nhttps://example.com\n";
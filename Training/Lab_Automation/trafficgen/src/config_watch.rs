@@ -0,0 +1,92 @@
+// Live config-file reload: watches a `--config` file for changes and
+// publishes freshly-parsed `Config`s through a `watch::channel`, mirroring
+// the pause mechanism `VirtualUser` already polls (see `wait_if_paused`).
+// A reload that fails to parse is logged and the previous config is kept,
+// so a mid-edit typo never brings down the running user fleet.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use console::style;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::watch;
+
+use crate::config::{self, Config};
+
+/// Watches `path` for changes and returns a `watch::Receiver` seeded with
+/// `initial`. `VirtualUser::run` re-reads this at the top of each site loop
+/// and depth iteration instead of holding a stale `Arc<Config>` clone.
+pub fn watch_config_file(path: String, initial: Arc<Config>) -> watch::Receiver<Arc<Config>> {
+    let (config_tx, config_rx) = watch::channel(initial);
+    let (event_tx, event_rx) = std::sync::mpsc::channel();
+
+    let watcher = RecommendedWatcher::new(
+        move |res| {
+            let _ = event_tx.send(res);
+        },
+        notify::Config::default(),
+    );
+    let mut watcher = match watcher {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!(
+                "{} Failed to start config watcher: {} (hot-reload disabled)",
+                style("[error]").red().bold(),
+                e
+            );
+            return config_rx;
+        }
+    };
+
+    if let Err(e) = watcher.watch(Path::new(&path), RecursiveMode::NonRecursive) {
+        eprintln!(
+            "{} Failed to watch {}: {} (hot-reload disabled)",
+            style("[error]").red().bold(),
+            path,
+            e
+        );
+        return config_rx;
+    }
+
+    tokio::task::spawn_blocking(move || {
+        // Keep the watcher alive for as long as this thread is running;
+        // it stops emitting events (and is dropped) once `event_rx` hangs up.
+        let _watcher = watcher;
+        for res in event_rx {
+            let event: Event = match res {
+                Ok(e) => e,
+                Err(e) => {
+                    eprintln!(
+                        "{} Config watch error: {}",
+                        style("[warn]").yellow().bold(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            if !event.kind.is_modify() && !event.kind.is_create() {
+                continue;
+            }
+            match config::load_config_file(&path) {
+                Ok(new_config) => {
+                    println!(
+                        "{} Reloaded configuration from {}",
+                        style("[ok]").green().bold(),
+                        path
+                    );
+                    let _ = config_tx.send(Arc::new(new_config));
+                }
+                Err(e) => {
+                    eprintln!(
+                        "{} Failed to reload {}: {} (keeping previous configuration)",
+                        style("[warn]").yellow().bold(),
+                        path,
+                        e
+                    );
+                }
+            }
+        }
+    });
+
+    config_rx
+}
@@ -3,6 +3,8 @@ use scraper::{Html, Selector};
 use std::collections::HashSet;
 use url::Url;
 
+use crate::scope::Scope;
+
 pub fn extract_links(html: &str, base_url: &Url) -> Vec<Url> {
     let document = Html::parse_document(html);
     let selector = Selector::parse("a[href]").expect("valid selector");
@@ -24,6 +26,31 @@ pub fn filter_same_domain<'a>(links: &'a [Url], domain: &str) -> Vec<&'a Url> {
         .collect()
 }
 
+/// Filters `links` against `scope`: first the cheap domain-suffix pass,
+/// then — only when a CIDR allow/deny list is actually configured — an
+/// async DNS-resolution check, so a hostname that looks in-scope by name
+/// but resolves outside the authorized ranges never reaches
+/// `pick_random_links`. Called between `filter_same_domain` and
+/// `pick_random_links` in the crawl loop.
+pub async fn filter_in_scope(links: Vec<Url>, scope: &Scope) -> Vec<Url> {
+    let by_domain = scope.filter_links_by_domain(links);
+
+    if scope.allow_cidrs.is_empty() && scope.deny_cidrs.is_empty() {
+        return by_domain;
+    }
+
+    let mut in_scope = Vec::with_capacity(by_domain.len());
+    for url in by_domain {
+        let Some(host) = url.host_str() else {
+            continue;
+        };
+        if scope.resolves_in_scope(host).await {
+            in_scope.push(url);
+        }
+    }
+    in_scope
+}
+
 pub fn pick_random_links(links: &[Url], max: usize, visited: &HashSet<String>) -> Vec<Url> {
     let mut unvisited: Vec<&Url> = links
         .iter()
@@ -38,6 +65,8 @@ pub fn pick_random_links(links: &[Url], max: usize, visited: &HashSet<String>) -
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ipnetwork::IpNetwork;
+    use std::str::FromStr;
 
     #[test]
     fn test_extract_links_absolute() {
@@ -114,4 +143,33 @@ mod tests {
         assert_eq!(picked.len(), 1);
         assert_eq!(picked[0].as_str(), "https://example.com/c");
     }
+
+    #[tokio::test]
+    async fn test_filter_in_scope_applies_cidr_rules_after_resolution() {
+        let scope = Scope {
+            // `localhost` may also resolve to `::1`, which must be allowed
+            // too since every resolved address is checked.
+            allow_cidrs: vec![
+                IpNetwork::from_str("127.0.0.0/8").unwrap(),
+                IpNetwork::from_str("::1/128").unwrap(),
+            ],
+            deny_cidrs: vec![IpNetwork::from_str("10.0.0.0/8").unwrap()],
+            ..Default::default()
+        };
+        let links = vec![Url::parse("http://localhost/page").unwrap()];
+        let filtered = filter_in_scope(links, &scope).await;
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_filter_in_scope_deny_cidr_overrides_resolved_allow() {
+        let scope = Scope {
+            allow_cidrs: vec![IpNetwork::from_str("127.0.0.0/8").unwrap()],
+            deny_cidrs: vec![IpNetwork::from_str("127.0.0.1/32").unwrap()],
+            ..Default::default()
+        };
+        let links = vec![Url::parse("http://localhost/page").unwrap()];
+        let filtered = filter_in_scope(links, &scope).await;
+        assert!(filtered.is_empty());
+    }
 }
@@ -0,0 +1,70 @@
+// Auditable rotation history, appended as newline-delimited JSON so a
+// cyber-range scenario's identity changes can be reconstructed after the
+// fact.
+
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+#[derive(Serialize)]
+pub struct RotationRecord {
+    pub timestamp: u64,
+    pub adapter: String,
+    pub ip: String,
+    pub mac: String,
+    pub vendor: String,
+}
+
+impl RotationRecord {
+    pub fn new(adapter: &str, ip: &str, mac: &str, vendor: &str) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            timestamp,
+            adapter: adapter.to_string(),
+            ip: ip.to_string(),
+            mac: mac.to_string(),
+            vendor: vendor.to_string(),
+        }
+    }
+}
+
+/// Appends one rotation event to the ledger file as a single JSON line,
+/// creating the file if it doesn't exist yet.
+pub fn append_rotation(path: &str, record: &RotationRecord) -> Result<(), String> {
+    let line = serde_json::to_string(record)
+        .map_err(|e| format!("Failed to serialize rotation record: {}", e))?;
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open ledger {}: {}", path, e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("Failed to write ledger {}: {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_rotation_writes_line() {
+        let path = std::env::temp_dir()
+            .join("trafficgen_test_ledger.jsonl")
+            .to_str()
+            .unwrap()
+            .to_string();
+        std::fs::remove_file(&path).ok();
+
+        let record = RotationRecord::new("eth0", "10.0.0.50", "AA:BB:CC:DD:EE:FF", "Dell");
+        append_rotation(&path, &record).expect("append should succeed");
+        append_rotation(&path, &record).expect("append should succeed");
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content.contains("\"adapter\":\"eth0\""));
+        std::fs::remove_file(&path).ok();
+    }
+}
@@ -58,6 +58,64 @@ const OUI_DATABASE: &[(&str, [u8; 3])] = &[
     ("Fortinet", [0x00, 0x09, 0x0F]),
 ];
 
+/// Wraps an operator-pinned MAC address, e.g. `Config::host_mac`, so
+/// rotation code can treat pinned and randomly generated MACs uniformly.
+pub fn fixed_mac(address: &str) -> MacAddress {
+    MacAddress {
+        address: address.to_string(),
+        vendor: "pinned",
+    }
+}
+
+/// Generates a fresh locally-administered unicast MAC (first octet:
+/// universal/local bit set, individual/group bit clear), for the periodic
+/// rotation scheduler. Unlike `generate_mac`, it doesn't claim a real vendor
+/// OUI; its `"locally-administered"` vendor is one `profiles_for_vendor`
+/// doesn't recognize, so users re-pick a generic Linux desktop profile
+/// after a scheduled rotation.
+pub fn generate_locally_administered_mac() -> MacAddress {
+    let mut rng = rand::thread_rng();
+    let mut first_octet: u8 = rng.gen();
+    first_octet |= 0x02;
+    first_octet &= !0x01;
+
+    let address = format!(
+        "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+        first_octet,
+        rng.gen::<u8>(),
+        rng.gen::<u8>(),
+        rng.gen::<u8>(),
+        rng.gen::<u8>(),
+        rng.gen::<u8>(),
+    );
+
+    MacAddress {
+        address,
+        vendor: "locally-administered",
+    }
+}
+
+/// Reverse-looks-up the OUI vendor for a MAC address already on the wire,
+/// e.g. the host's real, untouched adapter MAC under
+/// `IpAssignMode::PerUserBinding`, where nothing ever rotates it. Falls back
+/// to `"unknown"` for prefixes absent from `OUI_DATABASE`, which
+/// `profiles_for_vendor` treats the same as any other unrecognized vendor.
+pub fn vendor_for_address(address: &str) -> &'static str {
+    let prefix: Vec<u8> = address
+        .splitn(4, ':')
+        .take(3)
+        .filter_map(|b| u8::from_str_radix(b, 16).ok())
+        .collect();
+    if prefix.len() != 3 {
+        return "unknown";
+    }
+    OUI_DATABASE
+        .iter()
+        .find(|(_, oui)| oui[..] == prefix[..])
+        .map(|(vendor, _)| *vendor)
+        .unwrap_or("unknown")
+}
+
 pub fn generate_mac() -> MacAddress {
     let mut rng = rand::thread_rng();
     let idx = rng.gen_range(0..OUI_DATABASE.len());
@@ -121,6 +179,13 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_fixed_mac_preserves_address() {
+        let mac = fixed_mac("AA:BB:CC:DD:EE:FF");
+        assert_eq!(mac.address, "AA:BB:CC:DD:EE:FF");
+        assert_eq!(mac.vendor, "pinned");
+    }
+
     #[test]
     fn test_generate_mac_randomness() {
         let macs: Vec<MacAddress> = (0..10).map(|_| generate_mac()).collect();
@@ -128,4 +193,39 @@ mod tests {
         let all_same = macs.iter().all(|m| m.address == *first);
         assert!(!all_same, "All 10 generated MACs were identical");
     }
+
+    #[test]
+    fn test_generate_locally_administered_mac_sets_bits() {
+        for _ in 0..20 {
+            let mac = generate_locally_administered_mac();
+            let first_byte = u8::from_str_radix(&mac.address[..2], 16).unwrap();
+            assert_eq!(first_byte & 0x02, 0x02, "Locally administered bit not set on {}", mac.address);
+            assert_eq!(first_byte & 0x01, 0, "Multicast bit set on {}", mac.address);
+            assert_eq!(mac.vendor, "locally-administered");
+        }
+    }
+
+    #[test]
+    fn test_generate_locally_administered_mac_randomness() {
+        let macs: Vec<MacAddress> = (0..10).map(|_| generate_locally_administered_mac()).collect();
+        let first = &macs[0].address;
+        let all_same = macs.iter().all(|m| m.address == *first);
+        assert!(!all_same, "All 10 generated MACs were identical");
+    }
+
+    #[test]
+    fn test_vendor_for_address_known_oui() {
+        assert_eq!(vendor_for_address("A8:51:AB:11:22:33"), "Apple");
+        assert_eq!(vendor_for_address("a8:51:ab:11:22:33"), "Apple");
+    }
+
+    #[test]
+    fn test_vendor_for_address_unknown_oui() {
+        assert_eq!(vendor_for_address("12:34:56:78:9A:BC"), "unknown");
+    }
+
+    #[test]
+    fn test_vendor_for_address_malformed() {
+        assert_eq!(vendor_for_address("not-a-mac"), "unknown");
+    }
 }
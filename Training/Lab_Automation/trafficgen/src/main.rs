@@ -1,8 +1,11 @@
 mod browser;
 mod config;
+mod config_watch;
 mod crawler;
+mod ledger;
 mod mac;
 mod network;
+mod scope;
 mod user_sim;
 
 use std::net::IpAddr;
@@ -12,11 +15,11 @@ use std::sync::Arc;
 use console::style;
 use dialoguer::{Confirm, Input, Select};
 use ipnetwork::IpNetwork;
-use rand::Rng;
-use tokio::sync::watch;
+use tokio::sync::{watch, Mutex};
 
 use config::Config;
-use network::AdapterInfo;
+use network::reachability::{self, ReachabilityState};
+use network::{AdapterInfo, IpAssignMode};
 use user_sim::VirtualUser;
 
 fn load_sites(path: &str) -> Vec<url::Url> {
@@ -53,6 +56,14 @@ fn load_sites(path: &str) -> Vec<url::Url> {
     sites
 }
 
+/// Looks for `--config <path>` on the command line, for unattended runs
+/// that bypass `prompt_config` entirely.
+fn parse_config_path() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let idx = args.iter().position(|a| a == "--config")?;
+    args.get(idx + 1).cloned()
+}
+
 fn check_root() {
     if unsafe { libc::geteuid() } != 0 {
         eprintln!(
@@ -64,7 +75,11 @@ fn check_root() {
     }
 }
 
-fn prompt_config(sites: Vec<url::Url>, adapters: &[AdapterInfo]) -> Config {
+fn prompt_config(
+    sites: Vec<url::Url>,
+    adapters: &[AdapterInfo],
+    detected: &network::DetectedDefaults,
+) -> Config {
     println!(
         "\n{}",
         style("=== trafficgen configuration ===").cyan().bold()
@@ -74,36 +89,45 @@ fn prompt_config(sites: Vec<url::Url>, adapters: &[AdapterInfo]) -> Config {
         .iter()
         .map(|a| format!("{} (MAC: {}, State: {})", a.name, a.mac, a.state))
         .collect();
+    let default_adapter_idx = detected
+        .adapter
+        .as_ref()
+        .and_then(|name| adapters.iter().position(|a| &a.name == name))
+        .unwrap_or(0);
     let adapter_idx = Select::new()
         .with_prompt("Select network adapter")
         .items(&adapter_names)
-        .default(0)
+        .default(default_adapter_idx)
         .interact()
         .expect("Failed to read selection");
     let adapter = adapters[adapter_idx].name.clone();
 
-    let cidr_str: String = Input::new()
-        .with_prompt("CIDR range for IP rotation (e.g., 10.0.0.0/24)")
-        .interact_text()
-        .expect("Failed to read input");
+    let mut cidr_prompt = Input::<String>::new()
+        .with_prompt("CIDR range for IP rotation (e.g., 10.0.0.0/24)");
+    if let Some(cidr) = detected.cidr {
+        cidr_prompt = cidr_prompt.default(cidr.to_string());
+    }
+    let cidr_str = cidr_prompt.interact_text().expect("Failed to read input");
     let cidr = IpNetwork::from_str(&cidr_str).unwrap_or_else(|e| {
         eprintln!("{} Invalid CIDR: {}", style("[error]").red().bold(), e);
         std::process::exit(1);
     });
 
-    let dns_str: String = Input::new()
-        .with_prompt("DNS server IP")
-        .interact_text()
-        .expect("Failed to read input");
+    let mut dns_prompt = Input::<String>::new().with_prompt("DNS server IP");
+    if let Some(dns) = detected.dns {
+        dns_prompt = dns_prompt.default(dns.to_string());
+    }
+    let dns_str = dns_prompt.interact_text().expect("Failed to read input");
     let dns = IpAddr::from_str(&dns_str).unwrap_or_else(|e| {
         eprintln!("{} Invalid DNS IP: {}", style("[error]").red().bold(), e);
         std::process::exit(1);
     });
 
-    let gw_str: String = Input::new()
-        .with_prompt("Gateway/router IP")
-        .interact_text()
-        .expect("Failed to read input");
+    let mut gw_prompt = Input::<String>::new().with_prompt("Gateway/router IP");
+    if let Some(gateway) = detected.gateway {
+        gw_prompt = gw_prompt.default(gateway.to_string());
+    }
+    let gw_str = gw_prompt.interact_text().expect("Failed to read input");
     let gateway = IpAddr::from_str(&gw_str).unwrap_or_else(|e| {
         eprintln!(
             "{} Invalid gateway IP: {}",
@@ -169,6 +193,21 @@ fn prompt_config(sites: Vec<url::Url>, adapters: &[AdapterInfo]) -> Config {
         std::process::exit(1);
     }
 
+    let ip_mode_idx = Select::new()
+        .with_prompt("IP assignment mode")
+        .items(&[
+            "Adapter rotation (reconfigure the adapter on a timer)",
+            "Per-user binding (pin each virtual user to its own source IP)",
+        ])
+        .default(0)
+        .interact()
+        .expect("Failed to read selection");
+    let ip_mode = if ip_mode_idx == 1 {
+        IpAssignMode::PerUserBinding
+    } else {
+        IpAssignMode::AdapterRotation
+    };
+
     Config {
         sites,
         adapter,
@@ -180,6 +219,15 @@ fn prompt_config(sites: Vec<url::Url>, adapters: &[AdapterInfo]) -> Config {
         site_switch_mins,
         num_users,
         max_depth: 5,
+        ip_mode,
+        host_mac: None,
+        egress_check_url: None,
+        enforce_scope_firewall: false,
+        pinned_identities: None,
+        ledger_path: config::default_ledger_path(),
+        browser_profile_weights: None,
+        dns_mode: config::default_dns_mode(),
+        scope: scope::Scope::default(),
     }
 }
 
@@ -204,21 +252,13 @@ fn display_summary(config: &Config) {
     println!("  Virtual users:    {}", config.num_users);
     println!("  Max crawl depth:  {}", config.max_depth);
     println!("  Sites:            {}", config.sites.len());
-}
-
-fn random_ip_from_cidr(cidr: &IpNetwork, gateway: &IpAddr) -> Result<IpAddr, String> {
-    let hosts: Vec<IpAddr> = cidr
-        .iter()
-        .filter(|ip| ip != gateway && *ip != cidr.network() && *ip != cidr.broadcast())
-        .collect();
-    if hosts.is_empty() {
-        return Err(format!("No valid hosts in CIDR range {}", cidr));
-    }
-    let idx = {
-        let mut rng = rand::thread_rng();
-        rng.gen_range(0..hosts.len())
-    };
-    Ok(hosts[idx])
+    println!(
+        "  IP assignment:    {}",
+        match config.ip_mode {
+            IpAssignMode::AdapterRotation => "adapter rotation",
+            IpAssignMode::PerUserBinding => "per-user binding",
+        }
+    );
 }
 
 #[tokio::main]
@@ -231,23 +271,61 @@ async fn main() {
 
     check_root();
 
-    let sites = load_sites("sites.txt");
-
-    let adapters = network::list_adapters().await.unwrap_or_else(|e| {
-        eprintln!("{} {}", style("[error]").red().bold(), e);
-        std::process::exit(1);
-    });
-    if adapters.is_empty() {
-        eprintln!(
-            "{} No suitable network adapters found",
-            style("[error]").red().bold()
+    let config_path = parse_config_path();
+    let config = if let Some(path) = &config_path {
+        let config = config::load_config_file(path).unwrap_or_else(|e| {
+            eprintln!("{} {}", style("[error]").red().bold(), e);
+            std::process::exit(1);
+        });
+        println!(
+            "{} Loaded configuration from {} ({} sites, unattended mode)",
+            style("[ok]").green().bold(),
+            path,
+            config.sites.len(),
         );
-        std::process::exit(1);
-    }
+        config
+    } else {
+        let sites = load_sites("sites.txt");
 
-    let config = prompt_config(sites, &adapters);
+        let adapters = network::list_adapters().await.unwrap_or_else(|e| {
+            eprintln!("{} {}", style("[error]").red().bold(), e);
+            std::process::exit(1);
+        });
+        if adapters.is_empty() {
+            eprintln!(
+                "{} No suitable network adapters found",
+                style("[error]").red().bold()
+            );
+            std::process::exit(1);
+        }
+
+        let detected = network::detect_defaults().await;
+        prompt_config(sites, &adapters, &detected)
+    };
     display_summary(&config);
 
+    if config_path.is_none() {
+        if let Ok(true) = Confirm::new()
+            .with_prompt("Save this configuration for unattended reuse (--config)?")
+            .default(false)
+            .interact()
+        {
+            let save_path: String = Input::new()
+                .with_prompt("Config file path (.toml or .json)")
+                .default("run.toml".to_string())
+                .interact_text()
+                .expect("Failed to read input");
+            match config::save_config_file(&save_path, &config) {
+                Ok(()) => println!(
+                    "{} Saved configuration to {}",
+                    style("[ok]").green().bold(),
+                    save_path
+                ),
+                Err(e) => eprintln!("{} {}", style("[error]").red().bold(), e),
+            }
+        }
+    }
+
     if !Confirm::new()
         .with_prompt("Start traffic generation?")
         .default(true)
@@ -270,12 +348,183 @@ async fn main() {
 
     let config = Arc::new(config);
 
+    // Only a `--config` file is a reload target; a config built interactively
+    // has no canonical file backing it, so its receiver just never updates.
+    let config_rx = match &config_path {
+        Some(path) => config_watch::watch_config_file(path.clone(), Arc::clone(&config)),
+        None => watch::channel(Arc::clone(&config)).1,
+    };
+
+    if config.enforce_scope_firewall {
+        if let Err(e) = network::firewall::install(&config).await {
+            eprintln!(
+                "{} Failed to install scope firewall: {}",
+                style("[error]").red().bold(),
+                e
+            );
+            if let Err(re) = network::restore_config(&original).await {
+                eprintln!("{} Failed to restore original config: {}", style("[error]").red().bold(), re);
+            }
+            std::process::exit(1);
+        }
+        println!("{} Scope firewall installed on {}", style("[ok]").green().bold(), config.adapter);
+    }
+
+    // Keeps the nftables backstop in sync with hot-reloaded config: without
+    // this, a `--config` edit that changes `scope`/`dns`/`gateway`/
+    // `dns_mode`/`egress_check_url`/`enforce_scope_firewall` would leave
+    // `install`'s rules enforcing the pre-reload config while the
+    // crawler-level `scope::Scope` check in `user_sim::run` (which does
+    // read `config_rx` live) moves on to the new one.
+    let firewall_watch_handle = {
+        let mut config_rx = config_rx.clone();
+        let mut last_config = Arc::clone(&config);
+        tokio::spawn(async move {
+            while config_rx.changed().await.is_ok() {
+                let new_config = config_rx.borrow().clone();
+                if network::firewall::inputs_changed(&last_config, &new_config) {
+                    if new_config.enforce_scope_firewall {
+                        match network::firewall::install(&new_config).await {
+                            Ok(()) => println!(
+                                "{} Scope firewall re-applied after config reload",
+                                style("[ok]").green().bold()
+                            ),
+                            Err(e) => eprintln!(
+                                "{} Failed to re-apply scope firewall after config reload: {}",
+                                style("[error]").red().bold(),
+                                e
+                            ),
+                        }
+                    } else if let Err(e) = network::firewall::teardown().await {
+                        eprintln!(
+                            "{} Failed to tear down scope firewall after config reload: {}",
+                            style("[error]").red().bold(),
+                            e
+                        );
+                    }
+                }
+                last_config = new_config;
+            }
+        })
+    };
+
     let (pause_tx, pause_rx) = watch::channel(false);
+    let link_state: Arc<Mutex<ReachabilityState>> = Arc::new(Mutex::new(ReachabilityState::None));
+
+    // Under per-user binding, assign each virtual user its own source
+    // address up front instead of rotating the whole adapter over time.
+    let mut assigned_ips: Vec<IpAddr> = Vec::new();
+    if matches!(config.ip_mode, IpAssignMode::PerUserBinding) {
+        if matches!(config.cidr, IpNetwork::V6(_)) {
+            if let Err(e) = network::enable_ipv6_nonlocal_bind(&config.adapter, &config.cidr).await {
+                eprintln!(
+                    "{} Failed to enable IPv6 non-local bind: {}",
+                    style("[error]").red().bold(),
+                    e
+                );
+                std::process::exit(1);
+            }
+            for i in 0..config.num_users {
+                if let Some(pinned) = config.pinned_identities.as_ref().and_then(|p| p.get(i)) {
+                    assigned_ips.push(pinned.ip);
+                    continue;
+                }
+                match network::random_ipv6_in_prefix(&config.cidr) {
+                    Ok(ip) => assigned_ips.push(ip),
+                    Err(e) => {
+                        eprintln!("{} {}", style("[error]").red().bold(), e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        } else {
+            for i in 0..config.num_users {
+                if let Some(pinned) = config.pinned_identities.as_ref().and_then(|p| p.get(i)) {
+                    if let Err(e) =
+                        network::add_secondary_ip(&config.adapter, &pinned.ip, config.cidr.prefix())
+                            .await
+                    {
+                        eprintln!("{} {}", style("[error]").red().bold(), e);
+                        std::process::exit(1);
+                    }
+                    assigned_ips.push(pinned.ip);
+                    continue;
+                }
+                let exclude: Vec<IpAddr> =
+                    assigned_ips.iter().cloned().chain([config.gateway]).collect();
+                match network::random_ip_from_cidr(&config.cidr, &exclude) {
+                    Ok(ip) => {
+                        if let Err(e) =
+                            network::add_secondary_ip(&config.adapter, &ip, config.cidr.prefix())
+                                .await
+                        {
+                            eprintln!("{} {}", style("[error]").red().bold(), e);
+                            std::process::exit(1);
+                        }
+                        assigned_ips.push(ip);
+                    }
+                    Err(e) => {
+                        eprintln!("{} {}", style("[error]").red().bold(), e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        println!(
+            "{} Per-user bind addresses: {}",
+            style("[rotate]").yellow().bold(),
+            assigned_ips
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+    }
+
+    // The MAC actually about to be on the wire: under adapter rotation,
+    // the MAC the initial rotation below is about to apply (computed here,
+    // not re-drawn there, so the device profile below and the wire MAC
+    // never diverge); under per-user binding, nothing ever rotates the
+    // adapter's own MAC, so the real one it already has.
+    let initial_mac = if matches!(config.ip_mode, IpAssignMode::AdapterRotation) {
+        Some(match &config.host_mac {
+            Some(pinned) => mac::fixed_mac(pinned),
+            None => mac::generate_mac(),
+        })
+    } else {
+        None
+    };
+    let initial_vendor: &'static str = match &initial_mac {
+        Some(m) => m.vendor,
+        None => original
+            .mac
+            .as_deref()
+            .map(mac::vendor_for_address)
+            .unwrap_or("unknown"),
+    };
+    // Broadcasts the vendor of whatever MAC is currently on the wire so
+    // `VirtualUser::run` can keep re-picking a coherent device profile as
+    // rotations (see `network::scheduler`) change it, mirroring
+    // `config_rx`/`pause_rx`.
+    let (device_vendor_tx, device_vendor_rx) = watch::channel(initial_vendor);
 
     let mut user_handles = Vec::new();
     let mut user_statuses = Vec::new();
     for i in 0..config.num_users {
-        let mut user = VirtualUser::new(i + 1, Arc::clone(&config), pause_rx.clone());
+        let bind_ip = assigned_ips.get(i).copied();
+        let pinned_vendor = config
+            .pinned_identities
+            .as_ref()
+            .and_then(|p| p.get(i))
+            .map(|pinned| mac::vendor_for_address(&pinned.mac));
+        let mut user = VirtualUser::new(
+            i + 1,
+            config_rx.clone(),
+            pause_rx.clone(),
+            device_vendor_rx.clone(),
+            bind_ip,
+            pinned_vendor,
+        );
         user_statuses.push(Arc::clone(&user.status));
         let handle = tokio::spawn(async move {
             user.run().await;
@@ -283,10 +532,10 @@ async fn main() {
         user_handles.push(handle);
     }
 
-    // Perform initial IP/MAC rotation
-    {
-        let new_mac = mac::generate_mac();
-        let new_ip = match random_ip_from_cidr(&config.cidr, &config.gateway) {
+    let rotation_handle = if matches!(config.ip_mode, IpAssignMode::AdapterRotation) {
+        // Perform initial IP/MAC rotation
+        let new_mac = initial_mac.expect("AdapterRotation always computes initial_mac above");
+        let new_ip = match network::random_ip_from_cidr(&config.cidr, &[config.gateway]) {
             Ok(ip) => ip,
             Err(e) => {
                 eprintln!("{} {}", style("[error]").red().bold(), e);
@@ -320,78 +569,47 @@ async fn main() {
                 style("[error]").red().bold(),
                 e
             );
+        } else {
+            let record =
+                ledger::RotationRecord::new(&config.adapter, &new_ip.to_string(), &new_mac.address, new_mac.vendor);
+            if let Err(e) = ledger::append_rotation(&config.ledger_path, &record) {
+                eprintln!("{} Failed to record rotation ledger: {}", style("[warn]").yellow().bold(), e);
+            }
+            let state =
+                reachability::gate_until_reachable(&pause_tx, &config.gateway, &config.dns, 5, std::time::Duration::from_secs(3))
+                    .await;
+            println!("{} Link state: {}", style("[link]").cyan().bold(), state);
+            *link_state.lock().await = state;
         }
-    }
-
-    // Spawn rotation timer
-    let config_rot = Arc::clone(&config);
-    let rotation_handle = tokio::spawn(async move {
-        let interval = std::time::Duration::from_secs(config_rot.rotation_interval_mins * 60);
-        loop {
-            tokio::time::sleep(interval).await;
 
-            // Signal pause and wait for in-flight requests to finish
-            let _ = pause_tx.send(true);
-            tokio::time::sleep(std::time::Duration::from_secs(5)).await;
-
-            let new_mac = mac::generate_mac();
-            let new_ip = match random_ip_from_cidr(&config_rot.cidr, &config_rot.gateway) {
-                Ok(ip) => ip,
-                Err(e) => {
-                    eprintln!(
-                        "{} CIDR exhaustion: {}",
-                        style("[error]").red().bold(),
-                        e,
-                    );
-                    let _ = pause_tx.send(false);
-                    continue;
-                }
-            };
-            let prefix = config_rot.cidr.prefix();
-
-            println!(
-                "\n{} Rotating: IP={}, MAC={} ({})",
-                style("[rotate]").yellow().bold(),
-                new_ip,
-                new_mac.address,
-                new_mac.vendor,
-            );
-
-            match network::execute_rotation(
-                &config_rot.adapter,
-                &new_mac.address,
-                &new_ip.to_string(),
-                prefix,
-                &config_rot.gateway.to_string(),
-                &config_rot.dns.to_string(),
+        // Spawn the periodic rotation scheduler
+        let config_rot = Arc::clone(&config);
+        let link_state_rot = Arc::clone(&link_state);
+        let pause_tx_rot = pause_tx.clone();
+        let user_statuses_rot = user_statuses.clone();
+        let device_vendor_tx_rot = device_vendor_tx.clone();
+        Some(tokio::spawn(async move {
+            network::scheduler::run(
+                config_rot,
+                pause_tx_rot,
+                link_state_rot,
+                user_statuses_rot,
+                device_vendor_tx_rot,
             )
-            .await
-            {
-                Ok(()) => {
-                    println!(
-                        "{} Rotation complete",
-                        style("[rotate]").yellow().bold(),
-                    );
-                }
-                Err(e) => {
-                    eprintln!(
-                        "{} Rotation failed: {}",
-                        style("[error]").red().bold(),
-                        e,
-                    );
-                }
-            }
-
-            let _ = pause_tx.send(false);
-        }
-    });
+            .await;
+        }))
+    } else {
+        None
+    };
 
     // Spawn status display
     let config_display = Arc::clone(&config);
+    let link_state_display = Arc::clone(&link_state);
     let status_handle = tokio::spawn(async move {
         loop {
             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
             println!("\n{}", style("--- Status ---").dim());
+            println!("  Link: {}", *link_state_display.lock().await);
             for status_lock in &user_statuses {
                 let s = status_lock.lock().await;
                 let url_display = if s.current_url.len() > 60 {
@@ -399,9 +617,13 @@ async fn main() {
                 } else {
                     s.current_url.clone()
                 };
+                let ip_display = s
+                    .assigned_ip
+                    .map(|ip| format!(" [{}]", ip))
+                    .unwrap_or_default();
                 println!(
-                    "  User {}: {} {} (depth {}/{})",
-                    s.user_id, s.state, url_display, s.depth, config_display.max_depth,
+                    "  User {}{}: {} {} (depth {}/{})",
+                    s.user_id, ip_display, s.state, url_display, s.depth, config_display.max_depth,
                 );
             }
         }
@@ -421,8 +643,11 @@ async fn main() {
         style("[stop]").red().bold(),
     );
 
-    rotation_handle.abort();
+    if let Some(h) = &rotation_handle {
+        h.abort();
+    }
     status_handle.abort();
+    firewall_watch_handle.abort();
     for h in &user_handles {
         h.abort();
     }
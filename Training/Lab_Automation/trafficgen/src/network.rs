@@ -1,5 +1,27 @@
+pub mod firewall;
+pub mod reachability;
+pub mod resolver;
+pub mod scheduler;
+
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use ipnetwork::IpNetwork;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use tokio::process::Command;
 
+/// How virtual users are given distinct source addresses.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub enum IpAssignMode {
+    /// Reconfigure the whole adapter's IP/MAC on a timer (the original
+    /// behavior); all virtual users share one address at any moment.
+    AdapterRotation,
+    /// Pin each virtual user's `reqwest::Client` to its own address drawn
+    /// from `Config::cidr`, so N users appear as N distinct hosts at once.
+    PerUserBinding,
+}
+
 pub struct OriginalConfig {
     pub ip: Option<String>,
     pub mac: Option<String>,
@@ -103,6 +125,81 @@ pub fn build_rotation_commands(
     .collect()
 }
 
+/// Suggested values for the interactive prompts, gathered from the host's
+/// current network state so the operator can usually just accept them.
+#[derive(Default)]
+pub struct DetectedDefaults {
+    pub adapter: Option<String>,
+    pub gateway: Option<IpAddr>,
+    pub cidr: Option<IpNetwork>,
+    pub dns: Option<IpAddr>,
+}
+
+/// Parses `/proc/net/route` and returns the `(interface, gateway)` of the
+/// default route (the row whose destination is `00000000`). Gateway is
+/// stored little-endian hex, as the kernel writes it.
+fn parse_default_route(route_table: &str) -> Option<(String, IpAddr)> {
+    for line in route_table.lines().skip(1) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 3 || fields[1] != "00000000" {
+            continue;
+        }
+        let gw_hex = fields[2];
+        if gw_hex == "00000000" {
+            continue;
+        }
+        let gw_le = u32::from_str_radix(gw_hex, 16).ok()?;
+        let octets = gw_le.to_le_bytes();
+        let gateway = IpAddr::from(octets);
+        return Some((fields[0].to_string(), gateway));
+    }
+    None
+}
+
+/// Parses the first IPv4 `inet a.b.c.d/nn` line out of `ip addr show`
+/// output, giving the on-link CIDR for that adapter.
+fn parse_inet_cidr(addr_show_output: &str) -> Option<IpNetwork> {
+    addr_show_output
+        .lines()
+        .find(|l| l.trim_start().starts_with("inet "))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .and_then(|cidr| IpNetwork::from_str(cidr).ok())
+}
+
+/// Parses the first `nameserver` entry out of `/etc/resolv.conf` contents.
+fn parse_first_nameserver(resolv_conf: &str) -> Option<IpAddr> {
+    resolv_conf.lines().find_map(|l| {
+        let l = l.trim();
+        l.strip_prefix("nameserver").and_then(|rest| rest.trim().parse().ok())
+    })
+}
+
+/// Detects the default gateway/egress adapter, that adapter's on-link CIDR,
+/// and the first configured DNS resolver, to prefill the interactive
+/// prompts in `prompt_config`.
+pub async fn detect_defaults() -> DetectedDefaults {
+    let mut defaults = DetectedDefaults::default();
+
+    if let Ok(route_table) = tokio::fs::read_to_string("/proc/net/route").await {
+        if let Some((adapter, gateway)) = parse_default_route(&route_table) {
+            defaults.gateway = Some(gateway);
+            defaults.adapter = Some(adapter);
+        }
+    }
+
+    if let Some(adapter) = &defaults.adapter {
+        if let Ok(output) = Command::new("ip").args(["-4", "addr", "show", "dev", adapter]).output().await {
+            defaults.cidr = parse_inet_cidr(&String::from_utf8_lossy(&output.stdout));
+        }
+    }
+
+    if let Ok(resolv_conf) = tokio::fs::read_to_string("/etc/resolv.conf").await {
+        defaults.dns = parse_first_nameserver(&resolv_conf);
+    }
+
+    defaults
+}
+
 pub async fn list_adapters() -> Result<Vec<AdapterInfo>, String> {
     let output = Command::new("ip")
         .args(["link", "show"])
@@ -235,6 +332,107 @@ pub async fn execute_rotation(
     Ok(())
 }
 
+/// Picks a random usable host address from `cidr`, excluding the network
+/// address, broadcast address, and anything in `exclude` (e.g. the gateway).
+pub fn random_ip_from_cidr(cidr: &IpNetwork, exclude: &[IpAddr]) -> Result<IpAddr, String> {
+    let hosts: Vec<IpAddr> = cidr
+        .iter()
+        .filter(|ip| !exclude.contains(ip) && *ip != cidr.network() && *ip != cidr.broadcast())
+        .collect();
+    if hosts.is_empty() {
+        return Err(format!("No valid hosts in CIDR range {}", cidr));
+    }
+    let idx = {
+        let mut rng = rand::thread_rng();
+        rng.gen_range(0..hosts.len())
+    };
+    Ok(hosts[idx])
+}
+
+/// Picks a random address within an IPv6 prefix, for use with non-local
+/// bind where every address in the prefix is routable without being
+/// individually assigned to an adapter.
+pub fn random_ipv6_in_prefix(prefix: &IpNetwork) -> Result<IpAddr, String> {
+    let IpNetwork::V6(v6) = prefix else {
+        return Err(format!("{} is not an IPv6 prefix", prefix));
+    };
+    let base = u128::from(v6.network());
+    let host_bits = 128 - v6.prefix();
+    let mut rng = rand::thread_rng();
+    let host_part: u128 = if host_bits >= 128 {
+        rng.gen()
+    } else {
+        rng.gen_range(0..(1u128 << host_bits))
+    };
+    Ok(IpAddr::V6((base | host_part).into()))
+}
+
+/// Adds `ip` as a secondary address on `adapter`, alongside whatever address
+/// it already holds. Used by per-user IP binding so each virtual user's
+/// bind address is actually routable from the adapter.
+pub async fn add_secondary_ip(adapter: &str, ip: &IpAddr, prefix_len: u8) -> Result<(), String> {
+    let output = Command::new("ip")
+        .args(["addr", "add", &format!("{}/{}", ip, prefix_len), "dev", adapter])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to add secondary address {}: {}", ip, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("File exists") {
+            return Err(format!("Failed to add secondary address {}: {}", ip, stderr));
+        }
+    }
+    Ok(())
+}
+
+/// Sets up non-local bind for an IPv6 prefix so that per-user traffic can be
+/// sourced from any address in the prefix without assigning each one to the
+/// adapter individually: the prefix is added to loopback, a local route for
+/// it is added on `adapter`, and `net.ipv6.ip_nonlocal_bind` is enabled.
+pub async fn enable_ipv6_nonlocal_bind(adapter: &str, prefix: &IpNetwork) -> Result<(), String> {
+    if !matches!(prefix, IpNetwork::V6(_)) {
+        return Err(format!("{} is not an IPv6 prefix", prefix));
+    }
+
+    let output = Command::new("ip")
+        .args(["-6", "addr", "add", "local", &prefix.to_string(), "dev", "lo"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to add {} to loopback: {}", prefix, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("File exists") {
+            return Err(format!("Failed to add {} to loopback: {}", prefix, stderr));
+        }
+    }
+
+    let output = Command::new("ip")
+        .args(["-6", "route", "add", "local", &prefix.to_string(), "dev", adapter])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to add local route for {}: {}", prefix, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("File exists") {
+            return Err(format!("Failed to add local route for {}: {}", prefix, stderr));
+        }
+    }
+
+    let output = Command::new("sysctl")
+        .args(["-w", "net.ipv6.ip_nonlocal_bind=1"])
+        .output()
+        .await
+        .map_err(|e| format!("Failed to set net.ipv6.ip_nonlocal_bind: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to set net.ipv6.ip_nonlocal_bind: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
 pub async fn restore_config(original: &OriginalConfig) -> Result<(), String> {
     if let Some(ref mac) = original.mac {
         let _ = Command::new("ip")
@@ -271,6 +469,9 @@ pub async fn restore_config(original: &OriginalConfig) -> Result<(), String> {
     if let Some(ref content) = original.resolv_conf {
         let _ = tokio::fs::write("/etc/resolv.conf", content).await;
     }
+    // Tear down the scope firewall unconditionally; a no-op if it was never
+    // installed (see `firewall::teardown`).
+    let _ = firewall::teardown().await;
     Ok(())
 }
 
@@ -341,6 +542,82 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_random_ip_from_cidr_excludes_gateway() {
+        use std::str::FromStr;
+        let cidr = IpNetwork::from_str("10.0.0.0/29").unwrap();
+        let gateway = IpAddr::from_str("10.0.0.1").unwrap();
+        for _ in 0..20 {
+            let ip = random_ip_from_cidr(&cidr, &[gateway]).unwrap();
+            assert_ne!(ip, gateway);
+            assert!(cidr.contains(ip));
+        }
+    }
+
+    #[test]
+    fn test_random_ipv6_in_prefix_stays_within_prefix() {
+        use std::str::FromStr;
+        let prefix = IpNetwork::from_str("fd00::/64").unwrap();
+        for _ in 0..20 {
+            let ip = random_ipv6_in_prefix(&prefix).unwrap();
+            assert!(prefix.contains(ip));
+        }
+    }
+
+    #[test]
+    fn test_random_ipv6_in_prefix_rejects_v4() {
+        use std::str::FromStr;
+        let cidr = IpNetwork::from_str("10.0.0.0/24").unwrap();
+        assert!(random_ipv6_in_prefix(&cidr).is_err());
+    }
+
+    #[test]
+    fn test_parse_default_route() {
+        let route_table = "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                            eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+                            eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        let (iface, gateway) = parse_default_route(route_table).unwrap();
+        assert_eq!(iface, "eth0");
+        assert_eq!(gateway, IpAddr::from_str("10.0.0.1").unwrap());
+    }
+
+    #[test]
+    fn test_parse_default_route_none() {
+        let route_table = "Iface\tDestination\tGateway\n\
+                            eth0\t0000000A\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n";
+        assert!(parse_default_route(route_table).is_none());
+    }
+
+    #[test]
+    fn test_parse_inet_cidr() {
+        let output = "2: eth0: <BROADCAST,MULTICAST,UP,LOWER_UP> mtu 1500\n\
+                       \x20\x20\x20\x20inet 10.0.0.5/24 brd 10.0.0.255 scope global eth0\n\
+                       \x20\x20\x20\x20valid_lft forever preferred_lft forever\n";
+        let cidr = parse_inet_cidr(output).unwrap();
+        assert_eq!(cidr, IpNetwork::from_str("10.0.0.5/24").unwrap());
+    }
+
+    #[test]
+    fn test_parse_inet_cidr_missing() {
+        let output = "2: eth0: <BROADCAST,MULTICAST> mtu 1500\n";
+        assert!(parse_inet_cidr(output).is_none());
+    }
+
+    #[test]
+    fn test_parse_first_nameserver() {
+        let resolv_conf = "# generated\nnameserver 1.1.1.1\nnameserver 8.8.8.8\n";
+        assert_eq!(
+            parse_first_nameserver(resolv_conf),
+            Some(IpAddr::from_str("1.1.1.1").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_first_nameserver_missing() {
+        let resolv_conf = "# generated\nsearch example.com\n";
+        assert_eq!(parse_first_nameserver(resolv_conf), None);
+    }
+
     #[test]
     fn test_is_valid_adapter_name() {
         assert!(is_valid_adapter_name("eth0"));
@@ -0,0 +1,182 @@
+// Packet-level backstop for `scope::Scope`: an nftables egress chain that
+// drops anything outside the configured CIDR ranges, so a bug (or a
+// deliberately malicious page) in the crawl path can't reach past what
+// `Config::scope` authorizes just because `crawler::filter_in_scope` missed
+// it. Installed at startup when `Config::enforce_scope_firewall` is set,
+// re-applied (or torn down) when a hot-reloaded config changes its inputs,
+// and always torn down on shutdown by `network::restore_config`.
+
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+
+use crate::config::Config;
+use crate::network::resolver::DnsMode;
+use crate::scope::Scope;
+
+const TABLE: &str = "trafficgen_scope";
+
+/// Installs an `inet trafficgen_scope` table with an `egress` output chain
+/// on `config.adapter`. Before any of `config.scope`'s CIDR rules are
+/// applied, the generator's own infrastructure — `config.dns`,
+/// `config.gateway`, the DoH upstreams (when `config.dns_mode` is `DoH`),
+/// and `config.egress_check_url` — is explicitly allowed, since none of
+/// that traffic is normally inside the crawl's in-scope CIDR ranges and the
+/// chain's default policy is drop. `deny_cidrs` entries are then dropped,
+/// `allow_cidrs` entries accepted, and everything else falls through to the
+/// default-drop policy. A `scope` with no CIDR rules at all has nothing to
+/// enforce at the packet level and is rejected rather than installing a
+/// chain that drops everything. Idempotent: safe to call again (e.g. after
+/// a hot-reloaded config changes `inputs_changed`) since it tears down any
+/// table left over from a previous call first.
+pub async fn install(config: &Config) -> Result<(), String> {
+    let scope = &config.scope;
+    if scope.allow_cidrs.is_empty() && scope.deny_cidrs.is_empty() {
+        return Err("enforce_scope_firewall is set but scope has no CIDR rules to enforce".to_string());
+    }
+
+    teardown().await?;
+
+    let mut infra_ips = vec![config.dns, config.gateway];
+    if let DnsMode::DoH { upstreams, .. } = &config.dns_mode {
+        for upstream in upstreams {
+            infra_ips.extend(resolve_url_host(upstream).await?);
+        }
+    }
+    if let Some(egress_check_url) = &config.egress_check_url {
+        infra_ips.extend(resolve_url_host(egress_check_url).await?);
+    }
+
+    for rule in build_firewall_rules(&config.adapter, scope, &infra_ips) {
+        super::run_cmd(&rule).await?;
+    }
+
+    Ok(())
+}
+
+/// Whether anything `install` depends on differs between `old` and `new`,
+/// so a config-reload watcher knows when the scope firewall needs to be
+/// re-applied (or torn down) instead of silently continuing to enforce
+/// stale rules while the crawler-level `Scope` check has already moved on.
+pub fn inputs_changed(old: &Config, new: &Config) -> bool {
+    old.enforce_scope_firewall != new.enforce_scope_firewall
+        || old.adapter != new.adapter
+        || old.scope != new.scope
+        || old.dns != new.dns
+        || old.gateway != new.gateway
+        || old.dns_mode != new.dns_mode
+        || old.egress_check_url != new.egress_check_url
+}
+
+/// Resolves `url_str`'s host to every address it currently answers with,
+/// for infrastructure (DoH upstreams, the egress-check endpoint) that's
+/// identified by hostname rather than a fixed IP.
+async fn resolve_url_host(url_str: &str) -> Result<Vec<IpAddr>, String> {
+    let url = url::Url::parse(url_str).map_err(|e| format!("Invalid URL '{}': {}", url_str, e))?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| format!("URL '{}' has no host to allow", url_str))?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("Failed to resolve '{}' for firewall allowlist: {}", host, e))?;
+    Ok(addrs.map(|a| a.ip()).collect())
+}
+
+/// Builds the ordered `nft` argv list `install` executes: table, chain
+/// (default-drop policy), an accept rule for each of `infra_ips` before any
+/// scope CIDR rule, then `scope.deny_cidrs` drops, then `scope.allow_cidrs`
+/// accepts. Pulled out as a pure function, mirroring
+/// `network::build_rotation_commands`, so the ordering invariant that makes
+/// this a safety backstop — infra allowed before scope is ever evaluated —
+/// is unit-testable without shelling out to `nft`.
+fn build_firewall_rules(adapter: &str, scope: &Scope, infra_ips: &[IpAddr]) -> Vec<Vec<String>> {
+    let mut rules = vec![
+        vec_args(&["nft", "add", "table", "inet", TABLE]),
+        vec_args(&[
+            "nft", "add", "chain", "inet", TABLE, "egress",
+            "{", "type", "filter", "hook", "output", "priority", "0", ";", "policy", "drop", ";", "}",
+        ]),
+    ];
+    rules.extend(infra_ips.iter().map(|ip| accept_rule(adapter, *ip)));
+    rules.extend(scope.deny_cidrs.iter().map(|cidr| cidr_rule(adapter, cidr, "drop")));
+    rules.extend(scope.allow_cidrs.iter().map(|cidr| cidr_rule(adapter, cidr, "accept")));
+    rules
+}
+
+fn accept_rule(adapter: &str, ip: IpAddr) -> Vec<String> {
+    let family = if ip.is_ipv6() { "ip6" } else { "ip" };
+    vec_args(&[
+        "nft", "add", "rule", "inet", TABLE, "egress",
+        "oifname", adapter, family, "daddr", &ip.to_string(), "accept",
+    ])
+}
+
+fn cidr_rule(adapter: &str, cidr: &IpNetwork, verdict: &str) -> Vec<String> {
+    let family = if cidr.is_ipv6() { "ip6" } else { "ip" };
+    vec_args(&[
+        "nft", "add", "rule", "inet", TABLE, "egress",
+        "oifname", adapter, family, "daddr", &cidr.to_string(), verdict,
+    ])
+}
+
+/// Deletes the `trafficgen_scope` table. Safe to call whether or not it was
+/// ever installed: `nft delete table` failing because the table doesn't
+/// exist is treated as success.
+pub async fn teardown() -> Result<(), String> {
+    match super::run_cmd(&vec_args(&["nft", "delete", "table", "inet", TABLE])).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.contains("No such file or directory") => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn vec_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|s| s.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_build_firewall_rules_allows_infra_before_scope_cidrs() {
+        let scope = Scope {
+            allow_domains: vec![],
+            deny_domains: vec![],
+            allow_cidrs: vec![IpNetwork::from_str("192.168.1.0/24").unwrap()],
+            deny_cidrs: vec![IpNetwork::from_str("10.0.1.0/24").unwrap()],
+        };
+        let infra_ips = vec![
+            IpAddr::from_str("8.8.8.8").unwrap(),
+            IpAddr::from_str("10.0.0.1").unwrap(),
+        ];
+        let rules = build_firewall_rules("eth0", &scope, &infra_ips);
+
+        assert_eq!(rules.len(), 6);
+        assert_eq!(rules[0], vec!["nft", "add", "table", "inet", TABLE]);
+        assert!(rules[1].contains(&"policy".to_string()) && rules[1].contains(&"drop".to_string()));
+
+        // Infra accepts come before any scope CIDR rule.
+        assert!(rules[2].contains(&"8.8.8.8".to_string()) && rules[2].contains(&"accept".to_string()));
+        assert!(rules[3].contains(&"10.0.0.1".to_string()) && rules[3].contains(&"accept".to_string()));
+
+        // Deny CIDRs come before allow CIDRs.
+        assert!(rules[4].contains(&"10.0.1.0/24".to_string()) && rules[4].contains(&"drop".to_string()));
+        assert!(rules[5].contains(&"192.168.1.0/24".to_string()) && rules[5].contains(&"accept".to_string()));
+    }
+
+    #[test]
+    fn test_build_firewall_rules_no_infra() {
+        let scope = Scope {
+            allow_domains: vec![],
+            deny_domains: vec![],
+            allow_cidrs: vec![IpNetwork::from_str("192.168.1.0/24").unwrap()],
+            deny_cidrs: vec![],
+        };
+        let rules = build_firewall_rules("eth0", &scope, &[]);
+        assert_eq!(rules.len(), 3);
+        assert!(rules[2].contains(&"192.168.1.0/24".to_string()));
+    }
+}
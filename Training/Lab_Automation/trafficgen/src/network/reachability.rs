@@ -0,0 +1,136 @@
+// Post-rotation connectivity classification. A rotation that lands on a
+// bad route or an onlink fallback can leave the host with no working path;
+// this probes outward in stages so traffic is held until there's somewhere
+// for it to go or the retry budget runs out.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use tokio::process::Command;
+use tokio::sync::watch;
+
+const CANARY_URL: &str = "https://connectivitycheck.gstatic.com/generate_204";
+
+/// Connectivity state, ordered from least to most reachable so states can
+/// be compared with `>=`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReachabilityState {
+    None,
+    LocalOnly,
+    GatewayReachable,
+    InternetReachable,
+}
+
+impl fmt::Display for ReachabilityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ReachabilityState::None => "none",
+            ReachabilityState::LocalOnly => "local-only",
+            ReachabilityState::GatewayReachable => "gateway-reachable",
+            ReachabilityState::InternetReachable => "internet-reachable",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+async fn probe_gateway(gateway: &IpAddr) -> bool {
+    Command::new("ping")
+        .args(["-c", "1", "-W", "2", &gateway.to_string()])
+        .output()
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+async fn probe_dns(dns: &IpAddr) -> bool {
+    Command::new("dig")
+        .args(["+time=2", "+tries=1", &format!("@{}", dns), "example.com"])
+        .output()
+        .await
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+async fn probe_internet(canary_url: &str) -> bool {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(5)).build() {
+        Ok(c) => c,
+        Err(_) => return false,
+    };
+    client
+        .head(canary_url)
+        .send()
+        .await
+        .map(|resp| resp.status().is_success() || resp.status().as_u16() == 204)
+        .unwrap_or(false)
+}
+
+/// Classifies connectivity by probing in order: gateway (ping), DNS
+/// (lookup against `dns`), then an HTTP HEAD to a canary URL.
+pub async fn classify(gateway: &IpAddr, dns: &IpAddr) -> ReachabilityState {
+    if !probe_gateway(gateway).await {
+        return ReachabilityState::None;
+    }
+    if !probe_dns(dns).await {
+        return ReachabilityState::LocalOnly;
+    }
+    if !probe_internet(CANARY_URL).await {
+        return ReachabilityState::GatewayReachable;
+    }
+    ReachabilityState::InternetReachable
+}
+
+/// Retries `classify` until it reaches at least `GatewayReachable` or
+/// `max_attempts` is exhausted, waiting `retry_delay` between attempts.
+pub async fn wait_until_reachable(
+    gateway: &IpAddr,
+    dns: &IpAddr,
+    max_attempts: u32,
+    retry_delay: Duration,
+) -> ReachabilityState {
+    let mut state = ReachabilityState::None;
+    for attempt in 0..max_attempts.max(1) {
+        state = classify(gateway, dns).await;
+        if state >= ReachabilityState::GatewayReachable {
+            break;
+        }
+        if attempt + 1 < max_attempts {
+            tokio::time::sleep(retry_delay).await;
+        }
+    }
+    state
+}
+
+/// Holds `pause_tx` at `true` while probing connectivity after a rotation,
+/// resuming traffic once the link reaches at least `GatewayReachable` or
+/// the retry budget is exhausted.
+pub async fn gate_until_reachable(
+    pause_tx: &watch::Sender<bool>,
+    gateway: &IpAddr,
+    dns: &IpAddr,
+    max_attempts: u32,
+    retry_delay: Duration,
+) -> ReachabilityState {
+    let _ = pause_tx.send(true);
+    let state = wait_until_reachable(gateway, dns, max_attempts, retry_delay).await;
+    let _ = pause_tx.send(false);
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reachability_state_ordering() {
+        assert!(ReachabilityState::InternetReachable > ReachabilityState::GatewayReachable);
+        assert!(ReachabilityState::GatewayReachable > ReachabilityState::LocalOnly);
+        assert!(ReachabilityState::LocalOnly > ReachabilityState::None);
+    }
+
+    #[test]
+    fn test_reachability_state_display() {
+        assert_eq!(ReachabilityState::None.to_string(), "none");
+        assert_eq!(ReachabilityState::InternetReachable.to_string(), "internet-reachable");
+    }
+}
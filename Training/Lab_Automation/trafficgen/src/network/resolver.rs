@@ -0,0 +1,471 @@
+// DNS-over-HTTPS resolver (RFC 8484), wired into `browser::build_client` as
+// a `reqwest::dns::Resolve` override so a rotated identity's lookups don't
+// leak plaintext DNS through `write_resolv_conf`'s single nameserver line.
+// `DnsMode::SystemResolvConf` keeps that plaintext path available as an
+// explicit fallback, selected via `Config::dns_mode`.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use base64::Engine;
+use rand::Rng;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use serde::{Deserialize, Serialize};
+
+const RECORD_TYPE_A: u16 = 1;
+const RECORD_TYPE_AAAA: u16 = 28;
+const UPSTREAM_BACKOFF: Duration = Duration::from_secs(30);
+
+pub fn default_ttl_floor_secs() -> u64 {
+    30
+}
+
+pub fn default_ttl_ceiling_secs() -> u64 {
+    3600
+}
+
+/// Mirrors trust-dns's `LookupIpStrategy`: which address families to query
+/// and how to combine their answers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LookupIpStrategy {
+    Ipv4Only,
+    Ipv6Only,
+    Ipv4thenIpv6,
+    Ipv4AndIpv6,
+}
+
+/// How `browser::build_client` resolves hostnames.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DnsMode {
+    /// Use the system resolver, i.e. whatever `write_resolv_conf` last wrote
+    /// to `/etc/resolv.conf`.
+    SystemResolvConf,
+    /// Resolve over DoH against `upstreams`, round-robining across them with
+    /// per-upstream failure backoff, caching answers for at least
+    /// `ttl_floor_secs` and at most `ttl_ceiling_secs`.
+    DoH {
+        upstreams: Vec<String>,
+        strategy: LookupIpStrategy,
+        #[serde(default = "default_ttl_floor_secs")]
+        ttl_floor_secs: u64,
+        #[serde(default = "default_ttl_ceiling_secs")]
+        ttl_ceiling_secs: u64,
+    },
+}
+
+/// Encodes a minimal DNS wire-format query for `host`/`record_type`
+/// (RFC 1035 section 4.1), the payload RFC 8484 carries as
+/// base64url(no padding) in the DoH request's `dns` query parameter.
+fn encode_query(host: &str, record_type: u16) -> Vec<u8> {
+    let id: u16 = rand::thread_rng().gen();
+    let mut packet = Vec::with_capacity(32);
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&0x0100u16.to_be_bytes()); // flags: RD=1
+    packet.extend_from_slice(&1u16.to_be_bytes()); // QDCOUNT
+    packet.extend_from_slice(&0u16.to_be_bytes()); // ANCOUNT
+    packet.extend_from_slice(&0u16.to_be_bytes()); // NSCOUNT
+    packet.extend_from_slice(&0u16.to_be_bytes()); // ARCOUNT
+
+    for label in host.trim_end_matches('.').split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0); // root label
+
+    packet.extend_from_slice(&record_type.to_be_bytes());
+    packet.extend_from_slice(&1u16.to_be_bytes()); // QCLASS IN
+    packet
+}
+
+/// Skips a (possibly pointer-compressed) DNS name starting at `offset`,
+/// returning the offset just past it.
+fn skip_name(packet: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *packet.get(offset)?;
+        if len & 0xC0 == 0xC0 {
+            return Some(offset + 2);
+        }
+        if len == 0 {
+            return Some(offset + 1);
+        }
+        offset += 1 + len as usize;
+    }
+}
+
+/// Parses the answer section of a DNS wire-format response, returning the
+/// decoded addresses and the minimum TTL among them (so the cache entry
+/// expires no later than the shortest-lived record).
+fn parse_response(packet: &[u8]) -> Result<(Vec<IpAddr>, Duration), String> {
+    if packet.len() < 12 {
+        return Err("DNS response shorter than header".to_string());
+    }
+    let ancount = u16::from_be_bytes([packet[6], packet[7]]) as usize;
+    let qdcount = u16::from_be_bytes([packet[4], packet[5]]) as usize;
+
+    let mut offset = 12;
+    for _ in 0..qdcount {
+        offset = skip_name(packet, offset).ok_or("Truncated question section")?;
+        offset += 4; // QTYPE + QCLASS
+    }
+
+    let mut addrs = Vec::new();
+    let mut min_ttl = u32::MAX;
+    for _ in 0..ancount {
+        offset = skip_name(packet, offset).ok_or("Truncated answer name")?;
+        let rtype = u16::from_be_bytes(
+            packet
+                .get(offset..offset + 2)
+                .ok_or("Truncated answer type")?
+                .try_into()
+                .unwrap(),
+        );
+        let ttl = u32::from_be_bytes(
+            packet
+                .get(offset + 4..offset + 8)
+                .ok_or("Truncated answer TTL")?
+                .try_into()
+                .unwrap(),
+        );
+        let rdlength = u16::from_be_bytes(
+            packet
+                .get(offset + 8..offset + 10)
+                .ok_or("Truncated answer rdlength")?
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        let rdata_start = offset + 10;
+        let rdata = packet
+            .get(rdata_start..rdata_start + rdlength)
+            .ok_or("Truncated answer rdata")?;
+
+        match rtype {
+            RECORD_TYPE_A if rdata.len() == 4 => {
+                addrs.push(IpAddr::V4(Ipv4Addr::new(
+                    rdata[0], rdata[1], rdata[2], rdata[3],
+                )));
+                min_ttl = min_ttl.min(ttl);
+            }
+            RECORD_TYPE_AAAA if rdata.len() == 16 => {
+                let octets: [u8; 16] = rdata.try_into().unwrap();
+                addrs.push(IpAddr::V6(Ipv6Addr::from(octets)));
+                min_ttl = min_ttl.min(ttl);
+            }
+            _ => {}
+        }
+        offset = rdata_start + rdlength;
+    }
+
+    if addrs.is_empty() {
+        return Err("No A/AAAA records in response".to_string());
+    }
+    let ttl = if min_ttl == u32::MAX {
+        Duration::from_secs(default_ttl_floor_secs())
+    } else {
+        Duration::from_secs(min_ttl as u64)
+    };
+    Ok((addrs, ttl))
+}
+
+struct CacheEntry {
+    addrs: Vec<IpAddr>,
+    expires_at: Instant,
+}
+
+/// A `reqwest::dns::Resolve` implementation that looks names up over DoH
+/// instead of the system resolver, round-robining across `upstreams` and
+/// skipping any that are in a failure backoff window. Cheap to `Clone`
+/// (every field is `Arc`-backed or `Copy`), which `resolve` relies on since
+/// `Resolve::resolve` only gets `&self` but needs an owned value to move
+/// into its boxed future.
+#[derive(Clone)]
+pub struct DohResolver {
+    upstreams: Arc<Vec<String>>,
+    strategy: LookupIpStrategy,
+    ttl_floor: Duration,
+    ttl_ceiling: Duration,
+    client: reqwest::Client,
+    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    backoff_until: Arc<Mutex<HashMap<String, Instant>>>,
+    next_upstream: Arc<AtomicUsize>,
+}
+
+impl DohResolver {
+    pub fn new(
+        upstreams: Vec<String>,
+        strategy: LookupIpStrategy,
+        ttl_floor: Duration,
+        ttl_ceiling: Duration,
+    ) -> Self {
+        // A hand-edited config can set `ttl_floor_secs > ttl_ceiling_secs`;
+        // swap rather than let `store`'s `Duration::clamp` panic on it.
+        let (ttl_floor, ttl_ceiling) = if ttl_floor <= ttl_ceiling {
+            (ttl_floor, ttl_ceiling)
+        } else {
+            (ttl_ceiling, ttl_floor)
+        };
+        Self {
+            upstreams: Arc::new(upstreams),
+            strategy,
+            ttl_floor,
+            ttl_ceiling,
+            client: reqwest::Client::new(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            backoff_until: Arc::new(Mutex::new(HashMap::new())),
+            next_upstream: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    fn cached(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(host)
+            .filter(|entry| entry.expires_at > Instant::now())
+            .map(|entry| entry.addrs.clone())
+    }
+
+    fn store(&self, host: &str, addrs: Vec<IpAddr>, ttl: Duration) {
+        let clamped = ttl.clamp(self.ttl_floor, self.ttl_ceiling);
+        self.cache.lock().unwrap().insert(
+            host.to_string(),
+            CacheEntry {
+                addrs,
+                expires_at: Instant::now() + clamped,
+            },
+        );
+    }
+
+    /// Picks the next upstream in round-robin order, skipping any still
+    /// inside their failure backoff window.
+    fn pick_upstream(&self) -> Option<&str> {
+        let backoff = self.backoff_until.lock().unwrap();
+        let now = Instant::now();
+        let len = self.upstreams.len();
+        if len == 0 {
+            return None;
+        }
+        let start = self.next_upstream.fetch_add(1, Ordering::Relaxed) % len;
+        (0..len)
+            .map(|offset| &self.upstreams[(start + offset) % len])
+            .find(|upstream| match backoff.get(upstream.as_str()) {
+                Some(until) => *until <= now,
+                None => true,
+            })
+            .map(|s| s.as_str())
+    }
+
+    fn mark_failure(&self, upstream: &str) {
+        self.backoff_until
+            .lock()
+            .unwrap()
+            .insert(upstream.to_string(), Instant::now() + UPSTREAM_BACKOFF);
+    }
+
+    async fn query_upstream(
+        &self,
+        upstream: &str,
+        host: &str,
+        record_type: u16,
+    ) -> Result<(Vec<IpAddr>, Duration), String> {
+        let query = encode_query(host, record_type);
+        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(query);
+        let resp = self
+            .client
+            .get(upstream)
+            .query(&[("dns", encoded)])
+            .header(reqwest::header::ACCEPT, "application/dns-message")
+            .send()
+            .await
+            .map_err(|e| format!("DoH request to {} failed: {}", upstream, e))?;
+        if !resp.status().is_success() {
+            return Err(format!("DoH upstream {} returned {}", upstream, resp.status()));
+        }
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| format!("Failed to read DoH response body: {}", e))?;
+        parse_response(&body)
+    }
+
+    /// Resolves `host`, trying the record types implied by `self.strategy`
+    /// and falling back to the next upstream on failure.
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>, String> {
+        if let Some(addrs) = self.cached(host) {
+            return Ok(addrs);
+        }
+
+        let record_types: &[u16] = match self.strategy {
+            LookupIpStrategy::Ipv4Only => &[RECORD_TYPE_A],
+            LookupIpStrategy::Ipv6Only => &[RECORD_TYPE_AAAA],
+            LookupIpStrategy::Ipv4thenIpv6 => &[RECORD_TYPE_A, RECORD_TYPE_AAAA],
+            LookupIpStrategy::Ipv4AndIpv6 => &[RECORD_TYPE_A, RECORD_TYPE_AAAA],
+        };
+
+        let mut addrs = Vec::new();
+        let mut min_ttl = self.ttl_ceiling;
+        let mut last_err = "No DoH upstreams configured".to_string();
+
+        for &record_type in record_types {
+            let mut resolved = false;
+            for _ in 0..self.upstreams.len().max(1) {
+                let Some(upstream) = self.pick_upstream() else {
+                    break;
+                };
+                match self.query_upstream(upstream, host, record_type).await {
+                    Ok((mut found, ttl)) => {
+                        addrs.append(&mut found);
+                        min_ttl = min_ttl.min(ttl);
+                        resolved = true;
+                        break;
+                    }
+                    Err(e) => {
+                        self.mark_failure(upstream);
+                        last_err = e;
+                    }
+                }
+            }
+            if !resolved && self.strategy == LookupIpStrategy::Ipv4Only
+                || !resolved && self.strategy == LookupIpStrategy::Ipv6Only
+            {
+                return Err(last_err);
+            }
+            if matches!(self.strategy, LookupIpStrategy::Ipv4thenIpv6) && !addrs.is_empty() {
+                break;
+            }
+        }
+
+        if addrs.is_empty() {
+            return Err(last_err);
+        }
+        self.store(host, addrs.clone(), min_ttl);
+        Ok(addrs)
+    }
+}
+
+impl Resolve for DohResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs = resolver
+                .lookup(&host)
+                .await
+                .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> { e.into() })?;
+            let socket_addrs: Vec<SocketAddr> =
+                addrs.into_iter().map(|ip| SocketAddr::new(ip, 0)).collect();
+            Ok(Box::new(socket_addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_response(qname: &str, answers: &[(u16, u32, Vec<u8>)]) -> Vec<u8> {
+        let mut packet = encode_query(qname, RECORD_TYPE_A);
+        packet[6] = 0;
+        packet[7] = answers.len() as u8;
+        for (rtype, ttl, rdata) in answers {
+            packet.extend_from_slice(&[0xC0, 0x0C]); // pointer to qname at offset 12
+            packet.extend_from_slice(&rtype.to_be_bytes());
+            packet.extend_from_slice(&1u16.to_be_bytes()); // CLASS IN
+            packet.extend_from_slice(&ttl.to_be_bytes());
+            packet.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            packet.extend_from_slice(rdata);
+        }
+        packet
+    }
+
+    #[test]
+    fn test_encode_query_sets_header_and_qname() {
+        let packet = encode_query("example.com", RECORD_TYPE_A);
+        assert_eq!(u16::from_be_bytes([packet[4], packet[5]]), 1); // QDCOUNT
+        assert_eq!(packet[12], 7); // "example" label length
+        assert_eq!(&packet[13..20], b"example");
+    }
+
+    #[test]
+    fn test_parse_response_extracts_a_record() {
+        let packet = build_response("example.com", &[(RECORD_TYPE_A, 300, vec![93, 184, 216, 34])]);
+        let (addrs, ttl) = parse_response(&packet).unwrap();
+        assert_eq!(addrs, vec![IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))]);
+        assert_eq!(ttl, Duration::from_secs(300));
+    }
+
+    #[test]
+    fn test_parse_response_extracts_aaaa_record() {
+        let octets = [0x26, 0x06, 0x28, 0x00, 2, 0x20, 0, 1, 2, 0x48, 0x18, 0x93, 0x25, 0xc8, 0x19, 0x46];
+        let packet = build_response("example.com", &[(RECORD_TYPE_AAAA, 60, octets.to_vec())]);
+        let (addrs, ttl) = parse_response(&packet).unwrap();
+        assert_eq!(addrs, vec![IpAddr::V6(Ipv6Addr::from(octets))]);
+        assert_eq!(ttl, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_response_picks_minimum_ttl() {
+        let packet = build_response(
+            "example.com",
+            &[
+                (RECORD_TYPE_A, 300, vec![1, 2, 3, 4]),
+                (RECORD_TYPE_A, 60, vec![5, 6, 7, 8]),
+            ],
+        );
+        let (addrs, ttl) = parse_response(&packet).unwrap();
+        assert_eq!(addrs.len(), 2);
+        assert_eq!(ttl, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse_response_rejects_empty_answers() {
+        let packet = build_response("example.com", &[]);
+        assert!(parse_response(&packet).is_err());
+    }
+
+    #[test]
+    fn test_pick_upstream_skips_backed_off() {
+        let resolver = DohResolver::new(
+            vec!["https://a.example/dns-query".to_string(), "https://b.example/dns-query".to_string()],
+            LookupIpStrategy::Ipv4Only,
+            Duration::from_secs(30),
+            Duration::from_secs(3600),
+        );
+        resolver.mark_failure("https://a.example/dns-query");
+        assert_eq!(resolver.pick_upstream(), Some("https://b.example/dns-query"));
+    }
+
+    #[test]
+    fn test_cache_round_trip_respects_ttl_floor() {
+        let resolver = DohResolver::new(
+            vec!["https://a.example/dns-query".to_string()],
+            LookupIpStrategy::Ipv4Only,
+            Duration::from_secs(120),
+            Duration::from_secs(3600),
+        );
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        resolver.store("example.com", vec![ip], Duration::from_secs(1));
+        let cached = resolver.cached("example.com").unwrap();
+        assert_eq!(cached, vec![ip]);
+        // The 1s TTL was raised to the 120s floor.
+        let expires_at = resolver.cache.lock().unwrap()["example.com"].expires_at;
+        assert!(expires_at >= Instant::now() + Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_new_swaps_inverted_ttl_bounds_instead_of_panicking() {
+        let resolver = DohResolver::new(
+            vec!["https://a.example/dns-query".to_string()],
+            LookupIpStrategy::Ipv4Only,
+            Duration::from_secs(3600),
+            Duration::from_secs(30),
+        );
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        // Would panic in `store`'s `Duration::clamp` if `new` hadn't
+        // reordered the inverted floor/ceiling it was given.
+        resolver.store("example.com", vec![ip], Duration::from_secs(1));
+        let cached = resolver.cached("example.com").unwrap();
+        assert_eq!(cached, vec![ip]);
+    }
+}
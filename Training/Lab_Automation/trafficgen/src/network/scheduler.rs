@@ -0,0 +1,197 @@
+// Drives `execute_rotation` on a jittered timer, since it is a one-shot
+// primitive with no caller-side coordination of its own (`restore_config`
+// stays with `main`'s shutdown path). The scheduler is what actually pauses the browsing fleet
+// before tearing the adapter down and resumes it once the link is back.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use console::style;
+use rand::Rng;
+use tokio::sync::{watch, Mutex};
+
+use crate::config::Config;
+use crate::ledger;
+use crate::mac;
+use crate::network::{self, reachability::{self, ReachabilityState}};
+use crate::user_sim::UserStatus;
+
+/// How long to wait for in-flight requests to reach the `paused` state
+/// before rotating anyway, so a stuck request can't wedge rotation forever.
+const USER_PAUSE_TIMEOUT: Duration = Duration::from_secs(15);
+/// Cap on how far the egress-check backoff can stretch the rotation
+/// interval, so a permanently-unhelpful echo endpoint doesn't stop rotation
+/// altogether.
+const MAX_BACKOFF_MULTIPLIER: f64 = 8.0;
+
+/// Matches `user_sim::add_jitter`'s ±30% spread, so the rotation cadence
+/// looks as organic as the request cadence it's pausing.
+fn jittered_interval(base_secs: f64) -> Duration {
+    let mut rng = rand::thread_rng();
+    let factor = rng.gen_range(0.7..1.3);
+    Duration::from_secs_f64(base_secs * factor)
+}
+
+/// Polls `statuses` until every user reports `"paused"` or `timeout`
+/// elapses, so rotation never yanks the adapter out from under a request
+/// that's still in flight.
+async fn wait_for_users_paused(statuses: &[Arc<Mutex<UserStatus>>], timeout: Duration) {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let mut all_paused = true;
+        for status in statuses {
+            if status.lock().await.state != "paused" {
+                all_paused = false;
+                break;
+            }
+        }
+        if all_paused || tokio::time::Instant::now() >= deadline {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Fetches the caller's public IP from a plain-text echo endpoint (e.g.
+/// `https://api.ipify.org`), for confirming a rotation actually changed the
+/// egress identity.
+async fn fetch_egress_ip(url: &str) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| format!("Failed to build egress-check client: {}", e))?;
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .map_err(|e| format!("Egress check request to {} failed: {}", url, e))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read egress check response from {}: {}", url, e))?;
+    Ok(body.trim().to_string())
+}
+
+/// Runs the periodic identity-rotation loop: sleeps a jittered interval
+/// (scaled up by `egress_check_url`'s backoff when the last rotation didn't
+/// change the observed public IP), pauses the fleet, rotates, then gates
+/// traffic back on through `reachability::gate_until_reachable`, which
+/// resumes once the link is usable or its retry budget is exhausted.
+pub async fn run(
+    config: Arc<Config>,
+    pause_tx: watch::Sender<bool>,
+    link_state: Arc<Mutex<ReachabilityState>>,
+    user_statuses: Vec<Arc<Mutex<UserStatus>>>,
+    device_vendor_tx: watch::Sender<&'static str>,
+) {
+    let mut backoff_multiplier = 1.0_f64;
+    let mut last_egress_ip: Option<String> = None;
+
+    loop {
+        let interval = jittered_interval(config.rotation_interval_mins as f64 * 60.0 * backoff_multiplier);
+        tokio::time::sleep(interval).await;
+
+        let _ = pause_tx.send(true);
+        wait_for_users_paused(&user_statuses, USER_PAUSE_TIMEOUT).await;
+
+        let new_mac = match &config.host_mac {
+            Some(pinned) => mac::fixed_mac(pinned),
+            None => mac::generate_locally_administered_mac(),
+        };
+        let new_ip = match network::random_ip_from_cidr(&config.cidr, &[config.gateway]) {
+            Ok(ip) => ip,
+            Err(e) => {
+                eprintln!("{} CIDR exhaustion: {}", style("[error]").red().bold(), e);
+                let _ = pause_tx.send(false);
+                continue;
+            }
+        };
+        let prefix = config.cidr.prefix();
+
+        println!(
+            "\n{} Rotating: IP={}, MAC={} ({})",
+            style("[rotate]").yellow().bold(),
+            new_ip,
+            new_mac.address,
+            new_mac.vendor,
+        );
+
+        match network::execute_rotation(
+            &config.adapter,
+            &new_mac.address,
+            &new_ip.to_string(),
+            prefix,
+            &config.gateway.to_string(),
+            &config.dns.to_string(),
+        )
+        .await
+        {
+            Ok(()) => {
+                println!("{} Rotation complete", style("[rotate]").yellow().bold());
+                // Keep every virtual user's device profile coherent with
+                // the MAC this rotation just put on the wire (see
+                // `user_sim::run`).
+                let _ = device_vendor_tx.send(new_mac.vendor);
+                let record = ledger::RotationRecord::new(
+                    &config.adapter,
+                    &new_ip.to_string(),
+                    &new_mac.address,
+                    new_mac.vendor,
+                );
+                if let Err(e) = ledger::append_rotation(&config.ledger_path, &record) {
+                    eprintln!(
+                        "{} Failed to record rotation ledger: {}",
+                        style("[warn]").yellow().bold(),
+                        e
+                    );
+                }
+
+                let state = reachability::gate_until_reachable(
+                    &pause_tx,
+                    &config.gateway,
+                    &config.dns,
+                    5,
+                    Duration::from_secs(3),
+                )
+                .await;
+                println!("{} Link state: {}", style("[link]").cyan().bold(), state);
+                *link_state.lock().await = state;
+
+                if let Some(url) = &config.egress_check_url {
+                    match fetch_egress_ip(url).await {
+                        Ok(ip) => {
+                            let changed = last_egress_ip.as_deref() != Some(ip.as_str());
+                            if changed {
+                                println!(
+                                    "{} Egress IP changed: {}",
+                                    style("[ok]").green().bold(),
+                                    ip
+                                );
+                                backoff_multiplier = 1.0;
+                            } else {
+                                backoff_multiplier = (backoff_multiplier * 2.0).min(MAX_BACKOFF_MULTIPLIER);
+                                println!(
+                                    "{} Egress IP unchanged ({}); backing off rotation cadence to {:.1}x",
+                                    style("[warn]").yellow().bold(),
+                                    ip,
+                                    backoff_multiplier,
+                                );
+                            }
+                            last_egress_ip = Some(ip);
+                        }
+                        Err(e) => {
+                            eprintln!(
+                                "{} Egress check failed: {}",
+                                style("[warn]").yellow().bold(),
+                                e
+                            );
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                eprintln!("{} Rotation failed: {}", style("[error]").red().bold(), e);
+                let _ = pause_tx.send(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Crawl-scope enforcement: domain-suffix and CIDR allow/deny lists that
+// bound what `crawler::filter_in_scope` lets a virtual user request, so a
+// recon engagement can't wander onto a host outside its authorized scope
+// just because it happened to be linked from an in-scope page.
+
+use std::net::IpAddr;
+
+use ipnetwork::IpNetwork;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Domain-suffix and CIDR allow/deny lists loaded from `Config::scope`.
+/// Denials always take precedence over allowances; an empty allow list
+/// means "no suffix/CIDR restriction" rather than "allow nothing".
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scope {
+    #[serde(default)]
+    pub allow_domains: Vec<String>,
+    #[serde(default)]
+    pub deny_domains: Vec<String>,
+    #[serde(default)]
+    pub allow_cidrs: Vec<IpNetwork>,
+    #[serde(default)]
+    pub deny_cidrs: Vec<IpNetwork>,
+}
+
+fn domain_matches(host: &str, suffix: &str) -> bool {
+    host.eq_ignore_ascii_case(suffix) || host.to_ascii_lowercase().ends_with(&format!(".{}", suffix.to_ascii_lowercase()))
+}
+
+impl Scope {
+    /// True when no allow/deny rule is configured at all, i.e. scope
+    /// enforcement has nothing to do.
+    pub fn is_empty(&self) -> bool {
+        self.allow_domains.is_empty()
+            && self.deny_domains.is_empty()
+            && self.allow_cidrs.is_empty()
+            && self.deny_cidrs.is_empty()
+    }
+
+    /// Whether `host` is in scope by the domain-suffix lists alone.
+    pub fn allows_domain(&self, host: &str) -> bool {
+        if self.deny_domains.iter().any(|d| domain_matches(host, d)) {
+            return false;
+        }
+        self.allow_domains.is_empty() || self.allow_domains.iter().any(|d| domain_matches(host, d))
+    }
+
+    /// Whether `ip` is in scope by the CIDR lists alone.
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        if self.deny_cidrs.iter().any(|c| c.contains(ip)) {
+            return false;
+        }
+        self.allow_cidrs.is_empty() || self.allow_cidrs.iter().any(|c| c.contains(ip))
+    }
+
+    /// Filters `links` down to those whose host passes `allows_domain`. Used
+    /// by `crawler::filter_in_scope` as the cheap first pass, before the
+    /// optional DNS-resolution check against the CIDR lists.
+    pub fn filter_links_by_domain(&self, links: Vec<Url>) -> Vec<Url> {
+        if self.allow_domains.is_empty() && self.deny_domains.is_empty() {
+            return links;
+        }
+        links
+            .into_iter()
+            .filter(|url| url.host_str().map(|h| self.allows_domain(h)).unwrap_or(false))
+            .collect()
+    }
+
+    /// Resolves `host` and checks that every address it resolves to is
+    /// permitted by `allows_ip`, for hosts whose name looks in-scope but
+    /// might land outside the authorized CIDR ranges once actually
+    /// resolved. A host that fails to resolve at all is treated as
+    /// out-of-scope rather than let through unchecked.
+    pub async fn resolves_in_scope(&self, host: &str) -> bool {
+        if self.allow_cidrs.is_empty() && self.deny_cidrs.is_empty() {
+            return true;
+        }
+        match tokio::net::lookup_host((host, 0)).await {
+            Ok(addrs) => {
+                let mut any = false;
+                for addr in addrs {
+                    any = true;
+                    if !self.allows_ip(addr.ip()) {
+                        return false;
+                    }
+                }
+                any
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_allows_domain_no_rules_allows_everything() {
+        let scope = Scope::default();
+        assert!(scope.allows_domain("anything.example.com"));
+    }
+
+    #[test]
+    fn test_allows_domain_respects_allow_list() {
+        let scope = Scope {
+            allow_domains: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(scope.allows_domain("example.com"));
+        assert!(scope.allows_domain("sub.example.com"));
+        assert!(!scope.allows_domain("other.com"));
+    }
+
+    #[test]
+    fn test_deny_domain_overrides_allow_list() {
+        let scope = Scope {
+            allow_domains: vec!["example.com".to_string()],
+            deny_domains: vec!["blocked.example.com".to_string()],
+            ..Default::default()
+        };
+        assert!(scope.allows_domain("example.com"));
+        assert!(!scope.allows_domain("blocked.example.com"));
+    }
+
+    #[test]
+    fn test_allows_ip_respects_deny_cidr() {
+        let scope = Scope {
+            deny_cidrs: vec![IpNetwork::from_str("10.0.0.0/24").unwrap()],
+            ..Default::default()
+        };
+        assert!(!scope.allows_ip(IpAddr::from_str("10.0.0.5").unwrap()));
+        assert!(scope.allows_ip(IpAddr::from_str("10.0.1.5").unwrap()));
+    }
+
+    #[test]
+    fn test_allows_ip_respects_allow_cidr() {
+        let scope = Scope {
+            allow_cidrs: vec![IpNetwork::from_str("192.168.1.0/24").unwrap()],
+            ..Default::default()
+        };
+        assert!(scope.allows_ip(IpAddr::from_str("192.168.1.10").unwrap()));
+        assert!(!scope.allows_ip(IpAddr::from_str("8.8.8.8").unwrap()));
+    }
+
+    #[test]
+    fn test_filter_links_by_domain() {
+        let scope = Scope {
+            allow_domains: vec!["example.com".to_string()],
+            ..Default::default()
+        };
+        let links = vec![
+            Url::parse("https://example.com/a").unwrap(),
+            Url::parse("https://evil.com/b").unwrap(),
+        ];
+        let filtered = scope.filter_links_by_domain(links);
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].host_str(), Some("example.com"));
+    }
+
+    #[test]
+    fn test_is_empty() {
+        assert!(Scope::default().is_empty());
+        let scope = Scope {
+            deny_domains: vec!["blocked.com".to_string()],
+            ..Default::default()
+        };
+        assert!(!scope.is_empty());
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::HashSet;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::{watch, Mutex};
 use rand::Rng;
 use url::Url;
 
 use crate::browser;
+use crate::browser::DeviceProfile;
 use crate::config::Config;
 use crate::crawler;
 
@@ -13,13 +15,40 @@ pub struct UserStatus {
     pub current_url: String,
     pub depth: usize,
     pub state: String,
+    pub assigned_ip: Option<IpAddr>,
 }
 
 pub struct VirtualUser {
     pub id: usize,
-    pub config: Arc<Config>,
+    /// The latest published config, re-read at the top of each site loop
+    /// and depth iteration instead of being captured once, so a hot-reload
+    /// (see `config_watch::watch_config_file`) takes effect without
+    /// restarting the user fleet.
+    pub config_rx: watch::Receiver<Arc<Config>>,
     pub pause_rx: watch::Receiver<bool>,
+    /// The OUI vendor of whatever MAC is actually on the wire right now
+    /// (the host's real adapter MAC under `IpAssignMode::PerUserBinding`,
+    /// or the rotated MAC under `IpAssignMode::AdapterRotation`, updated by
+    /// every rotation in `main`/`network::scheduler`), so `device_profile`
+    /// never drifts from the identity an inspector would actually see.
+    pub device_vendor_rx: watch::Receiver<&'static str>,
     pub status: Arc<Mutex<UserStatus>>,
+    /// Source address this user's client is pinned to, when running under
+    /// `IpAssignMode::PerUserBinding`. `None` under adapter rotation, where
+    /// every user shares whatever address the adapter currently holds.
+    pub assigned_ip: Option<IpAddr>,
+    /// This user's `Config::pinned_identities[i].mac` vendor, when pinned.
+    /// Overrides `device_vendor_rx` for this user only: a pinned identity's
+    /// MAC is part of a fixed per-user identity distinct from whatever MAC
+    /// is actually on the shared adapter, so its device profile should
+    /// track that instead of the fleet-wide wire vendor.
+    pub pinned_vendor: Option<&'static str>,
+    /// This user's device identity, re-derived at the top of every site
+    /// loop from `pinned_vendor` (if set) or `device_vendor_rx` otherwise
+    /// (see `browser::profile_for_vendor`), so it tracks the real MAC
+    /// vendor instead of being fixed at a value that's never actually on
+    /// the wire.
+    pub device_profile: &'static DeviceProfile,
 }
 
 fn add_jitter(base_secs: f64) -> f64 {
@@ -29,43 +58,65 @@ fn add_jitter(base_secs: f64) -> f64 {
 }
 
 impl VirtualUser {
-    pub fn new(id: usize, config: Arc<Config>, pause_rx: watch::Receiver<bool>) -> Self {
+    pub fn new(
+        id: usize,
+        config_rx: watch::Receiver<Arc<Config>>,
+        pause_rx: watch::Receiver<bool>,
+        device_vendor_rx: watch::Receiver<&'static str>,
+        assigned_ip: Option<IpAddr>,
+        pinned_vendor: Option<&'static str>,
+    ) -> Self {
         let status = Arc::new(Mutex::new(UserStatus {
             user_id: id,
             current_url: String::new(),
             depth: 0,
             state: "starting".to_string(),
+            assigned_ip,
         }));
+        let device_profile = browser::profile_for_vendor(
+            pinned_vendor.unwrap_or(*device_vendor_rx.borrow()),
+            config_rx.borrow().browser_profile_weights.as_ref(),
+        );
         Self {
             id,
-            config,
+            config_rx,
             pause_rx,
+            device_vendor_rx,
             status,
+            assigned_ip,
+            pinned_vendor,
+            device_profile,
         }
     }
 
     pub async fn run(&mut self) {
         loop {
+            let config = self.config_rx.borrow().clone();
+            self.device_profile = browser::profile_for_vendor(
+                self.pinned_vendor.unwrap_or(*self.device_vendor_rx.borrow()),
+                config.browser_profile_weights.as_ref(),
+            );
             let site_idx = {
                 let mut rng = rand::thread_rng();
-                rng.gen_range(0..self.config.sites.len())
+                rng.gen_range(0..config.sites.len())
             };
-            let site = self.config.sites[site_idx].clone();
+            let site = config.sites[site_idx].clone();
             let domain = site.host_str().unwrap_or("").to_string();
 
-            let client = match browser::build_client() {
-                Ok(c) => c,
-                Err(e) => {
-                    eprintln!("[user {}] Failed to build HTTP client: {}", self.id, e);
-                    tokio::time::sleep(std::time::Duration::from_secs(10)).await;
-                    continue;
-                }
-            };
+            let client =
+                match browser::build_client(self.device_profile, self.assigned_ip, &config.dns_mode) {
+                    Ok(c) => c,
+                    Err(e) => {
+                        eprintln!("[user {}] Failed to build HTTP client: {}", self.id, e);
+                        tokio::time::sleep(std::time::Duration::from_secs(10)).await;
+                        continue;
+                    }
+                };
 
             let mut visited = HashSet::new();
             let mut current_url = site.clone();
             let site_switch_deadline = tokio::time::Instant::now()
-                + std::time::Duration::from_secs(self.config.site_switch_mins * 60);
+                + std::time::Duration::from_secs(config.site_switch_mins * 60);
 
             'site_loop: loop {
                 if tokio::time::Instant::now() >= site_switch_deadline {
@@ -76,8 +127,15 @@ impl VirtualUser {
 
                 let mut depth = 0;
                 visited.insert(current_url.to_string());
+                // `None` until the first page of this site load has been
+                // fetched, so the homepage request never carries a Referer.
+                let mut referer: Option<Url> = None;
 
-                while depth < self.config.max_depth {
+                loop {
+                    let config = self.config_rx.borrow().clone();
+                    if depth >= config.max_depth {
+                        break;
+                    }
                     {
                         let mut s = self.status.lock().await;
                         s.current_url = current_url.to_string();
@@ -85,7 +143,12 @@ impl VirtualUser {
                         s.state = "browsing".to_string();
                     }
 
-                    let body = match client.get(current_url.as_str()).send().await {
+                    let mut request = client.get(current_url.as_str());
+                    if let Some(referer_url) = &referer {
+                        request = request.header(reqwest::header::REFERER, referer_url.as_str());
+                    }
+
+                    let body = match request.send().await {
                         Ok(resp) => match resp.text().await {
                             Ok(text) => text,
                             Err(e) => {
@@ -104,12 +167,13 @@ impl VirtualUser {
                             break;
                         }
                     };
+                    referer = Some(current_url.clone());
 
                     {
                         let mut s = self.status.lock().await;
                         s.state = "waiting".to_string();
                     }
-                    let delay = add_jitter(self.config.request_delay_mins * 60.0);
+                    let delay = add_jitter(config.request_delay_mins * 60.0);
                     tokio::time::sleep(std::time::Duration::from_secs_f64(delay)).await;
 
                     self.wait_if_paused().await;
@@ -119,7 +183,8 @@ impl VirtualUser {
                         .into_iter()
                         .cloned()
                         .collect();
-                    let candidates = crawler::pick_random_links(&same_domain, 1, &visited);
+                    let in_scope = crawler::filter_in_scope(same_domain, &config.scope).await;
+                    let candidates = crawler::pick_random_links(&in_scope, 1, &visited);
 
                     if let Some(next_url) = candidates.into_iter().next() {
                         visited.insert(next_url.to_string());